@@ -1,19 +1,65 @@
+use crate::git::large_files::FileLimits;
+use crate::git::message::MessageStyle;
+use crate::git::protect::{BranchProtection, ProtectedAction};
+use std::path::PathBuf;
 use std::time::Duration;
 
+/// One-shot subcommands that run instead of the TUI.
+pub enum Command {
+    /// `kris-watcher squash [-m <message>]`
+    Squash { message: Option<String> },
+}
+
 pub struct Config {
+    pub command: Option<Command>,
     pub autosave_mode: bool,
+    /// Autosave to `refs/kris/snapshots/<branch>` instead of the branch itself.
+    pub snapshot_mode: bool,
+    /// Push to the upstream after every commit.
+    pub push_mode: bool,
+    /// Let autosave commit inside dirty submodules too.
+    pub submodules: bool,
     pub loop_delay: Duration,
+    pub poll_interval: Duration,
     pub shiggy_mode: bool,
     pub audio_alert_mode: bool,
+    pub fs_events: bool,
+    pub message_style: MessageStyle,
+    /// Branches commits never land on directly.
+    pub protection: BranchProtection,
+    /// What autosave leaves unstaged.
+    pub file_limits: FileLimits,
+    pub repo_paths: Vec<PathBuf>,
+    pub scan_dir: Option<PathBuf>,
 }
 //aa
 impl Config {
     pub fn new() -> Result<Self, String> {
         let args: Vec<String> = std::env::args().collect();
         let autosave_mode = args.contains(&"--autosave".to_string());
+        let snapshot_mode = args.contains(&"--snapshot".to_string());
+        let push_mode = args.contains(&"--push".to_string());
+        let submodules = args.contains(&"--submodules".to_string());
         let shiggy_mode = args.contains(&"--shiggy".to_string());
         let audio_alert_mode = args.contains(&"-a".to_string());
+        let fs_events = args.contains(&"--fs-events".to_string());
+        let message_style = MessageStyle {
+            conventional: args.contains(&"--conventional".to_string()),
+            uwu: !args.contains(&"--no-uwu".to_string()),
+        };
         let mut loop_delay = Duration::from_secs(15 * 60);
+        let mut poll_interval = Duration::from_secs(5);
+        if let Some(pos) = args
+            .iter()
+            .position(|s| s == "-p" || s == "--poll-interval")
+        {
+            if let Some(value_str) = args.get(pos + 1) {
+                poll_interval = parse_duration(value_str).unwrap_or(poll_interval);
+            }
+        }
+
+        let mut repo_paths = Vec::new();
+        let mut scan_dir = None;
 
         if let Some(pos) = args.iter().position(|s| s == "-l" || s == "--loop-delay") {
             if let Some(value_str) = args.get(pos + 1) {
@@ -21,11 +67,99 @@ impl Config {
             }
         }
 
+        for (pos, arg) in args.iter().enumerate() {
+            if arg == "--repo" {
+                match args.get(pos + 1) {
+                    Some(value) => repo_paths.push(PathBuf::from(value)),
+                    None => return Err("--repo needs a path".to_string()),
+                }
+            }
+        }
+
+        if let Some(pos) = args.iter().position(|s| s == "--scan") {
+            match args.get(pos + 1) {
+                Some(value) => scan_dir = Some(PathBuf::from(value)),
+                None => return Err("--scan needs a directory".to_string()),
+            }
+        }
+
+        let mut patterns = Vec::new();
+        for (pos, arg) in args.iter().enumerate() {
+            if arg == "--protect" {
+                match args.get(pos + 1) {
+                    Some(value) => patterns.push(value.clone()),
+                    None => return Err("--protect needs a branch pattern".to_string()),
+                }
+            }
+        }
+        if patterns.is_empty() {
+            patterns = ["main", "master", "release/*"].map(String::from).to_vec();
+        }
+        if args.contains(&"--no-protect".to_string()) {
+            patterns.clear();
+        }
+        let action = match args.iter().position(|s| s == "--on-protected") {
+            Some(pos) => match args.get(pos + 1).map(String::as_str) {
+                Some("refuse") => ProtectedAction::Refuse,
+                Some("wip") => ProtectedAction::Wip,
+                _ => return Err("--on-protected needs refuse or wip".to_string()),
+            },
+            None => ProtectedAction::Refuse,
+        };
+        let protection = BranchProtection { patterns, action };
+
+        let mut file_limits = FileLimits {
+            max_size: 10 * 1024 * 1024,
+            binaries: false,
+        };
+        if let Some(pos) = args.iter().position(|s| s == "--max-file-size") {
+            match args.get(pos + 1).map(|v| parse_size(v)) {
+                Some(Ok(size)) => file_limits.max_size = size,
+                _ => return Err("--max-file-size needs a size like 500K, 10M or 1G".to_string()),
+            }
+        }
+        if let Some(pos) = args.iter().position(|s| s == "--binary-files") {
+            match args.get(pos + 1).map(String::as_str) {
+                Some("allow") => file_limits.binaries = true,
+                Some("skip") => file_limits.binaries = false,
+                _ => return Err("--binary-files needs allow or skip".to_string()),
+            }
+        }
+
+        let command = match args.get(1).map(String::as_str) {
+            Some("squash") => {
+                let message = match args.iter().position(|s| s == "-m" || s == "--message") {
+                    Some(pos) => match args.get(pos + 1) {
+                        Some(value) => Some(value.clone()),
+                        None => return Err("-m needs a message".to_string()),
+                    },
+                    None => None,
+                };
+                Some(Command::Squash { message })
+            }
+            _ => None,
+        };
+
+        if repo_paths.is_empty() && scan_dir.is_none() {
+            repo_paths.push(PathBuf::from("."));
+        }
+
         Ok(Self {
+            command,
             autosave_mode,
+            snapshot_mode,
+            push_mode,
+            submodules,
             loop_delay,
+            poll_interval,
             shiggy_mode,
             audio_alert_mode,
+            fs_events,
+            message_style,
+            protection,
+            file_limits,
+            repo_paths,
+            scan_dir,
         })
     }
 }
@@ -55,3 +189,40 @@ fn parse_duration(s: &str) -> Result<Duration, String> {
         _ => Err("Invalid time unit".to_string()),
     }
 }
+
+/// Bytes, with an optional K, M or G suffix (powers of 1024).
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 1024),
+        Some((i, 'M' | 'm')) => (&s[..i], 1024 * 1024),
+        Some((i, 'G' | 'g')) => (&s[..i], 1024 * 1024 * 1024),
+        _ => (s, 1),
+    };
+    let value: f64 = number.parse().map_err(|_| "Invalid size".to_string())?;
+    // `as u64` would quietly turn these into 0, which means no limit at all
+    if !value.is_finite() || value < 0.0 {
+        return Err("Invalid size".to_string());
+    }
+    Ok((value * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes_with_suffixes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1.5k"), Ok(1536));
+        assert_eq!(parse_size("0"), Ok(0));
+    }
+
+    #[test]
+    fn rejects_sizes_that_would_mean_no_limit() {
+        for size in ["-1", "-5M", "nan", "NaNM", "inf", "", "M", "ten"] {
+            assert!(parse_size(size).is_err(), "{}", size);
+        }
+    }
+}
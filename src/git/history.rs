@@ -0,0 +1,52 @@
+use crate::git::{is_autosave_commit, REFLOG_PREFIX};
+use anyhow::Result;
+use git2::{Oid, Repository};
+use std::collections::HashSet;
+use std::path::Path;
+
+pub struct HistoryEntry {
+    pub id: Oid,
+    /// Seconds since the epoch.
+    pub time: i64,
+    pub author: String,
+    pub summary: String,
+    /// Made by kris rather than by hand.
+    pub by_kris: bool,
+}
+
+/// The last `limit` commits reachable from HEAD, newest first. Empty when
+/// there are no commits yet.
+pub fn recent_commits(repo_path: &Path, limit: usize) -> Result<Vec<HistoryEntry>> {
+    let repo = Repository::open(repo_path)?;
+    let Ok(head) = repo.head() else {
+        return Ok(Vec::new());
+    };
+    let from_kris = kris_commits(&repo, head.name().unwrap_or("HEAD"));
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    let mut entries = Vec::with_capacity(limit);
+    for id in revwalk.take(limit) {
+        let commit = repo.find_commit(id?)?;
+        entries.push(HistoryEntry {
+            id: commit.id(),
+            time: commit.time().seconds(),
+            author: commit.author().name().unwrap_or("?").to_string(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+            by_kris: from_kris.contains(&commit.id()) || is_autosave_commit(&commit),
+        });
+    }
+    Ok(entries)
+}
+
+/// Every commit the reflog of `ref_name` says kris put there.
+fn kris_commits(repo: &Repository, ref_name: &str) -> HashSet<Oid> {
+    let Ok(reflog) = repo.reflog(ref_name) else {
+        return HashSet::new();
+    };
+    reflog
+        .iter()
+        .filter(|e| e.message().is_some_and(|m| m.starts_with(REFLOG_PREFIX)))
+        .map(|e| e.id_new())
+        .collect()
+}
@@ -0,0 +1,155 @@
+use anyhow::{bail, Result};
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Only the tail of a chatty hook makes it into the TUI.
+const MAX_OUTPUT_LINES: usize = 10;
+
+/// Runs the hooks `git commit` would run, from `core.hooksPath` or `.git/hooks`.
+pub struct Hooks {
+    dir: PathBuf,
+    workdir: PathBuf,
+    git_dir: PathBuf,
+}
+
+impl Hooks {
+    pub fn new(repo: &Repository) -> Result<Self> {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow::anyhow!("bare repositories have no work tree"))?
+            .to_path_buf();
+        let git_dir = repo.path().to_path_buf();
+        // like git, a relative hooksPath is relative to the work tree
+        let dir = match repo.config()?.get_path("core.hooksPath") {
+            Ok(path) if path.is_relative() => workdir.join(path),
+            Ok(path) => path,
+            Err(_) => git_dir.join("hooks"),
+        };
+        Ok(Self {
+            dir,
+            workdir,
+            git_dir,
+        })
+    }
+
+    /// `pre-commit`, run against `index_file`: the real index, or the
+    /// temporary one holding only the picked paths.
+    pub fn pre_commit(&self, index_file: &Path) -> Result<()> {
+        self.run("pre-commit", &[], index_file)
+    }
+
+    /// Passes `message` through `prepare-commit-msg` and `commit-msg` via
+    /// `.git/COMMIT_EDITMSG`, the way git does, and returns what they left
+    /// behind with the whitespace cleaned up.
+    pub fn commit_msg(&self, message: &str, index_file: &Path) -> Result<String> {
+        let has_hooks =
+            self.find("prepare-commit-msg").is_some() || self.find("commit-msg").is_some();
+        if !has_hooks {
+            return Ok(message.to_string());
+        }
+
+        let msg_file = self.git_dir.join("COMMIT_EDITMSG");
+        fs::write(&msg_file, message)?;
+        let msg_arg = msg_file.to_string_lossy().into_owned();
+        self.run("prepare-commit-msg", &[&msg_arg, "message"], index_file)?;
+        self.run("commit-msg", &[&msg_arg], index_file)?;
+
+        let message = cleanup(&fs::read_to_string(&msg_file)?);
+        if message.is_empty() {
+            bail!("a commit-msg hook left an empty commit message");
+        }
+        Ok(message)
+    }
+
+    /// `post-commit` can't stop anything anymore, a failure is only reported.
+    pub fn post_commit(&self, index_file: &Path) -> Result<()> {
+        self.run("post-commit", &[], index_file)
+    }
+
+    fn find(&self, name: &str) -> Option<PathBuf> {
+        let path = self.dir.join(name);
+        let meta = fs::metadata(&path).ok()?;
+        // git skips hooks that aren't executable, with a hint we don't need
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if meta.permissions().mode() & 0o111 == 0 {
+                return None;
+            }
+        }
+        meta.is_file().then_some(path)
+    }
+
+    fn run(&self, name: &str, args: &[&str], index_file: &Path) -> Result<()> {
+        let Some(hook) = self.find(name) else {
+            return Ok(());
+        };
+
+        // hooks are usually shell scripts, which only unix can run directly
+        let mut command = if cfg!(unix) {
+            Command::new(&hook)
+        } else {
+            let mut command = Command::new("sh");
+            command.arg(&hook);
+            command
+        };
+        let output = command
+            .args(args)
+            .current_dir(&self.workdir)
+            .env("GIT_INDEX_FILE", index_file)
+            .env("GIT_EDITOR", ":")
+            .stdin(Stdio::null())
+            .output()?;
+
+        if !output.status.success() {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+            let tail = &lines[lines.len().saturating_sub(MAX_OUTPUT_LINES)..];
+            bail!("{} hook failed\n{}", name, tail.join("\n"));
+        }
+        Ok(())
+    }
+}
+
+/// What `git commit -m` does, `--cleanup=whitespace`: trailing whitespace
+/// and blank lines at either end go, runs of blank lines become one. Lines
+/// starting with `#` stay, `#42 fix crash` is a message, not a comment.
+fn cleanup(message: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in message.lines().map(str::trim_end) {
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    if lines.last() == Some(&"") {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleanup_keeps_hash_lines() {
+        assert_eq!(cleanup("#42 fix crash\n"), "#42 fix crash");
+        assert_eq!(
+            cleanup("Release notes\n\n# Added\n- hooks\n"),
+            "Release notes\n\n# Added\n- hooks"
+        );
+    }
+
+    #[test]
+    fn cleanup_trims_whitespace_and_blank_lines() {
+        assert_eq!(
+            cleanup("\n\nsummary  \n\n\n\nbody\t\n  indented\n\n\n"),
+            "summary\n\nbody\n  indented"
+        );
+        assert_eq!(cleanup(" \n\t\n"), "");
+    }
+}
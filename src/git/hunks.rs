@@ -0,0 +1,82 @@
+use anyhow::{bail, Result};
+use git2::{ApplyLocation, ApplyOptions, Diff, DiffOptions, Patch, Repository};
+use std::path::Path;
+
+pub struct HunkLine {
+    /// `+`, `-` or ` ` like in a unified diff.
+    pub origin: char,
+    pub content: String,
+}
+
+pub struct Hunk {
+    pub header: String,
+    pub lines: Vec<HunkLine>,
+}
+
+/// Index -> work tree diff of a single tracked file. Both the hunk view and
+/// `apply_hunks` go through here so the hunk numbering always lines up.
+fn file_diff<'a>(repo: &'a Repository, path: &Path) -> Result<Diff<'a>> {
+    let mut opts = DiffOptions::new();
+    opts.pathspec(path).disable_pathspec_match(true);
+    Ok(repo.diff_index_to_workdir(None, Some(&mut opts))?)
+}
+
+pub fn load_hunks(repo_path: &Path, path: &Path) -> Result<Vec<Hunk>> {
+    let repo = Repository::open(repo_path)?;
+    let diff = file_diff(&repo, path)?;
+    let patch = Patch::from_diff(&diff, 0)?;
+    match patch {
+        Some(patch) => from_patch(&patch),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn from_patch(patch: &Patch) -> Result<Vec<Hunk>> {
+    let mut hunks = Vec::new();
+    for hunk_idx in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_idx)?;
+        let mut lines = Vec::with_capacity(line_count);
+        for line_idx in 0..line_count {
+            let line = patch.line_in_hunk(hunk_idx, line_idx)?;
+            lines.push(HunkLine {
+                origin: line.origin(),
+                content: String::from_utf8_lossy(line.content())
+                    .trim_end_matches('\n')
+                    .to_string(),
+            });
+        }
+        hunks.push(Hunk {
+            header: String::from_utf8_lossy(hunk.header())
+                .trim_end()
+                .to_string(),
+            lines,
+        });
+    }
+    Ok(hunks)
+}
+
+/// Stages the hunks of `path` whose flag in `selected` is set and leaves the
+/// rest in the work tree only. libgit2 writes the index itself here.
+pub fn apply_hunks(repo: &Repository, path: &Path, selected: &[bool]) -> Result<()> {
+    let diff = file_diff(repo, path)?;
+    let hunk_count = match Patch::from_diff(&diff, 0)? {
+        Some(patch) => patch.num_hunks(),
+        None => 0,
+    };
+    if hunk_count != selected.len() {
+        bail!(
+            "{} changed since its hunks were picked, pick them again",
+            path.display()
+        );
+    }
+
+    let mut next_hunk = 0;
+    let mut opts = ApplyOptions::new();
+    opts.hunk_callback(|_| {
+        let keep = selected[next_hunk];
+        next_hunk += 1;
+        keep
+    });
+    repo.apply(&diff, ApplyLocation::Index, Some(&mut opts))?;
+    Ok(())
+}
@@ -0,0 +1,268 @@
+use anyhow::{bail, Context, Result};
+use git2::{AttrCheckFlags, Index, Repository};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// How much of a file git looks at to decide whether it's binary.
+const BINARY_PROBE: usize = 8000;
+
+/// What autosave is allowed to stage on its own. Files picked in the popup
+/// are the user's call and never checked.
+#[derive(Debug, Clone, Copy)]
+pub struct FileLimits {
+    /// In bytes, 0 for no limit.
+    pub max_size: u64,
+    /// Binary files git doesn't track yet get committed too. Ones already
+    /// tracked always do, someone decided that before.
+    pub binaries: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum SkipReason {
+    TooLarge(u64),
+    Binary,
+}
+
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+impl fmt::Display for SkippedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            SkipReason::TooLarge(size) => {
+                write!(f, "{} ({})", self.path.display(), human_size(size))
+            }
+            SkipReason::Binary => write!(f, "{} (binary)", self.path.display()),
+        }
+    }
+}
+
+impl FileLimits {
+    /// Why `path` (relative to `workdir`) should stay out of the commit, if it
+    /// should. `tracked` is the index as it was before staging anything.
+    pub fn check(&self, workdir: &Path, tracked: &Index, path: &Path) -> Option<SkipReason> {
+        let full_path = workdir.join(path);
+        let meta = fs::symlink_metadata(&full_path).ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        if self.max_size > 0 && meta.len() > self.max_size {
+            return Some(SkipReason::TooLarge(meta.len()));
+        }
+        if !self.binaries && tracked.get_path(path, 0).is_none() && is_binary(&full_path) {
+            return Some(SkipReason::Binary);
+        }
+        None
+    }
+}
+
+/// git's own heuristic: a NUL byte near the start.
+fn is_binary(path: &Path) -> bool {
+    let mut buffer = [0; BINARY_PROBE];
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    let read = file.read(&mut buffer).unwrap_or(0);
+    buffer[..read].contains(&0)
+}
+
+/// `.gitattributes` routes `path` through the LFS filter. libgit2 doesn't run
+/// that filter, so these have to be staged by git itself.
+pub fn is_lfs(repo: &Repository, path: &Path) -> bool {
+    matches!(
+        repo.get_attr(path, "filter", AttrCheckFlags::FILE_THEN_INDEX),
+        Ok(Some("lfs"))
+    )
+}
+
+/// `git add` with the real git, which runs the LFS clean filter.
+pub fn stage_with_git(workdir: &Path, paths: &[PathBuf]) -> Result<()> {
+    let mut args: Vec<&OsStr> = vec!["add".as_ref(), "--".as_ref()];
+    args.extend(paths.iter().map(|p| p.as_os_str()));
+    git(workdir, &args)
+}
+
+/// Appends the paths to the work tree's `.gitignore`, anchored to the root so
+/// nothing else with the same name gets caught.
+pub fn ignore(workdir: &Path, paths: &[PathBuf]) -> Result<()> {
+    let gitignore = workdir.join(".gitignore");
+    let existing = fs::read_to_string(&gitignore).unwrap_or_default();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&gitignore)?;
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        writeln!(file)?;
+    }
+    for path in paths {
+        writeln!(file, "/{}", escape_pattern(&path.to_string_lossy()))?;
+    }
+    Ok(())
+}
+
+/// `git lfs track` for exactly these files, then stages them and the updated
+/// `.gitattributes` so the next commit stores pointers instead of the blobs.
+pub fn track_with_lfs(workdir: &Path, paths: &[PathBuf]) -> Result<()> {
+    let mut args: Vec<&OsStr> = vec!["lfs".as_ref(), "track".as_ref(), "--filename".as_ref()];
+    args.extend(paths.iter().map(|p| p.as_os_str()));
+    git(workdir, &args).context("is git-lfs installed?")?;
+
+    let mut staged = vec![PathBuf::from(".gitattributes")];
+    staged.extend(paths.iter().cloned());
+    stage_with_git(workdir, &staged)
+}
+
+fn git(workdir: &Path, args: &[&OsStr]) -> Result<()> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(workdir)
+        .stdin(Stdio::null())
+        .output()
+        .context("couldn't run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed\n{}",
+            args.first().map_or("".into(), |a| a.to_string_lossy()),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Wildcards would mean something else in .gitignore.
+fn escape_pattern(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_repo::TestRepo;
+
+    fn limits(max_size: u64, binaries: bool) -> FileLimits {
+        FileLimits { max_size, binaries }
+    }
+
+    #[test]
+    fn check_skips_files_over_the_size_limit() {
+        let test = TestRepo::new();
+        test.write("big.txt", &"x".repeat(100));
+        test.write("small.txt", "x");
+        let index = test.repo().index().unwrap();
+        let big = Path::new("big.txt");
+
+        assert!(matches!(
+            limits(10, true).check(test.path(), &index, big),
+            Some(SkipReason::TooLarge(100))
+        ));
+        assert!(limits(100, true).check(test.path(), &index, big).is_none());
+        assert!(limits(0, true).check(test.path(), &index, big).is_none());
+        assert!(limits(10, true)
+            .check(test.path(), &index, Path::new("small.txt"))
+            .is_none());
+    }
+
+    #[test]
+    fn check_skips_only_untracked_binaries() {
+        let test = TestRepo::new();
+        fs::write(test.path().join("tracked.bin"), b"a\0b").unwrap();
+        test.commit_all("first");
+        fs::write(test.path().join("new.bin"), b"a\0b").unwrap();
+        test.write("new.txt", "text");
+        let index = test.repo().index().unwrap();
+
+        assert!(matches!(
+            limits(0, false).check(test.path(), &index, Path::new("new.bin")),
+            Some(SkipReason::Binary)
+        ));
+        assert!(limits(0, true)
+            .check(test.path(), &index, Path::new("new.bin"))
+            .is_none());
+        assert!(limits(0, false)
+            .check(test.path(), &index, Path::new("tracked.bin"))
+            .is_none());
+        assert!(limits(0, false)
+            .check(test.path(), &index, Path::new("new.txt"))
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_leaves_symlinks_alone() {
+        let test = TestRepo::new();
+        test.write("big.txt", &"x".repeat(100));
+        std::os::unix::fs::symlink("big.txt", test.path().join("link")).unwrap();
+        let index = test.repo().index().unwrap();
+
+        assert!(limits(10, false)
+            .check(test.path(), &index, Path::new("link"))
+            .is_none());
+    }
+
+    #[test]
+    fn escape_pattern_escapes_wildcards() {
+        assert_eq!(escape_pattern("data/plain.csv"), "data/plain.csv");
+        assert_eq!(escape_pattern(r"a*b?[c]\d"), r"a\*b\?\[c]\\d");
+    }
+
+    #[test]
+    fn ignore_anchors_paths_to_the_root() {
+        let test = TestRepo::new();
+        test.write(".gitignore", "target");
+        ignore(
+            test.path(),
+            &[PathBuf::from("data/big.csv"), PathBuf::from("*.bin")],
+        )
+        .unwrap();
+
+        let gitignore = fs::read_to_string(test.path().join(".gitignore")).unwrap();
+        assert_eq!(gitignore, "target\n/data/big.csv\n/\\*.bin\n");
+    }
+
+    #[test]
+    fn ignore_creates_a_missing_gitignore() {
+        let test = TestRepo::new();
+        ignore(test.path(), &[PathBuf::from("big.iso")]).unwrap();
+
+        let gitignore = fs::read_to_string(test.path().join(".gitignore")).unwrap();
+        assert_eq!(gitignore, "/big.iso\n");
+    }
+
+    #[test]
+    fn human_size_picks_a_unit() {
+        assert_eq!(human_size(0), "0 B");
+        assert_eq!(human_size(1023), "1023 B");
+        assert_eq!(human_size(1024), "1.0 KB");
+        assert_eq!(human_size(1536 * 1024), "1.5 MB");
+        assert_eq!(human_size(10 * 1024 * 1024 * 1024), "10.0 GB");
+        assert_eq!(human_size(2048 * 1024 * 1024 * 1024 * 1024), "2048.0 TB");
+    }
+}
@@ -0,0 +1,363 @@
+use crate::git::ChangeKind;
+use crate::uwu;
+use git2::{Delta, Diff};
+use std::path::{Path, PathBuf};
+
+/// Only this many files get their own line in the body, big commits get a
+/// "... and N more" instead.
+const MAX_BODY_FILES: usize = 20;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MessageStyle {
+    /// Prefix the summary with a Conventional Commits type inferred from paths.
+    pub conventional: bool,
+    /// Keep kwis' signature line at the end of the message.
+    pub uwu: bool,
+}
+
+struct PathChange {
+    kind: ChangeKind,
+    path: PathBuf,
+    old_path: Option<PathBuf>,
+}
+
+/// Builds a commit message out of a staged diff (HEAD -> index).
+pub fn from_diff(diff: &Diff, style: MessageStyle) -> String {
+    let changes: Vec<PathChange> = diff
+        .deltas()
+        .map(|delta| {
+            let path = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let old_path = match delta.status() {
+                Delta::Renamed => delta.old_file().path().map(Path::to_path_buf),
+                _ => None,
+            };
+            PathChange {
+                kind: match delta.status() {
+                    Delta::Untracked => ChangeKind::Added,
+                    status => ChangeKind::from_delta(status),
+                },
+                path,
+                old_path,
+            }
+        })
+        .collect();
+
+    generate(&changes, style)
+}
+
+fn generate(changes: &[PathChange], style: MessageStyle) -> String {
+    let mut modules: Vec<String> = Vec::new();
+    for change in changes {
+        let module = module_of(&change.path);
+        if !modules.contains(&module) {
+            modules.push(module);
+        }
+    }
+
+    let mut message = summary(changes, &modules);
+    if style.conventional {
+        let kind = conventional_type(changes);
+        let scope = match modules.as_slice() {
+            [module] if module != kind => format!("({})", module),
+            _ => String::new(),
+        };
+        message = format!("{}{}: {}", kind, scope, lowercase_first(&message));
+    }
+
+    if changes.len() > 1 {
+        message.push('\n');
+        for change in changes.iter().take(MAX_BODY_FILES) {
+            message.push_str(&format!("\n- {}", describe(change)));
+        }
+        if changes.len() > MAX_BODY_FILES {
+            message.push_str(&format!(
+                "\n- ... and {} more",
+                changes.len() - MAX_BODY_FILES
+            ));
+        }
+    }
+
+    if style.uwu {
+        message.push_str("\n\n");
+        message.push_str(uwu::get_commit_flavour());
+    }
+    message
+}
+
+fn summary(changes: &[PathChange], modules: &[String]) -> String {
+    match changes {
+        [] => "Empty commit".to_string(),
+        // describe() is lowercase for the body list
+        [change] => uppercase_first(&describe(change)),
+        _ => {
+            let verb = if changes.iter().all(|c| c.kind == changes[0].kind) {
+                verb(changes[0].kind)
+            } else {
+                "Update"
+            };
+            let scope = if modules.len() <= 3 {
+                modules.join(", ")
+            } else {
+                format!("{} modules", modules.len())
+            };
+            format!("{} {} files in {}", verb, changes.len(), scope)
+        }
+    }
+}
+
+fn describe(change: &PathChange) -> String {
+    match &change.old_path {
+        Some(old_path) => format!("rename {} to {}", old_path.display(), change.path.display()),
+        None => format!(
+            "{} {}",
+            verb(change.kind).to_lowercase(),
+            change.path.display()
+        ),
+    }
+}
+
+fn verb(kind: ChangeKind) -> &'static str {
+    match kind {
+        ChangeKind::Added | ChangeKind::Untracked => "Add",
+        ChangeKind::Deleted => "Remove",
+        ChangeKind::Renamed => "Rename",
+        ChangeKind::Modified | ChangeKind::TypeChange => "Update",
+    }
+}
+
+/// `src/tui/ui.rs` belongs to `tui`, `src/main.rs` to `main`, `docs/x.md` to
+/// `docs` and a file at the root is its own module.
+fn module_of(path: &Path) -> String {
+    let mut components = path.components().map(|c| c.as_os_str());
+    let first = components.next().unwrap_or_default();
+    let module = match (first.to_str(), components.next()) {
+        (Some("src"), Some(second)) => Path::new(second),
+        _ => Path::new(first),
+    };
+    module
+        .file_stem()
+        .unwrap_or(module.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn conventional_type(changes: &[PathChange]) -> &'static str {
+    let all = |f: fn(&Path) -> bool| changes.iter().all(|c| f(&c.path));
+
+    if all(|p| p.starts_with("docs") || p.extension().is_some_and(|e| e == "md")) {
+        "docs"
+    } else if all(is_test_path) {
+        "test"
+    } else if all(|p| {
+        p == Path::new("Cargo.toml") || p == Path::new("Cargo.lock") || p == Path::new("build.rs")
+    }) {
+        "build"
+    } else if changes.iter().all(|c| c.kind == ChangeKind::Added) {
+        "feat"
+    } else {
+        "chore"
+    }
+}
+
+/// Inside a `tests/` directory, or named like a test: `test_*`, `*_test`,
+/// `*_tests`, `*.test.*` or `*.spec.*`. `latest.rs` and `contest.rs` aren't.
+fn is_test_path(path: &Path) -> bool {
+    if path.components().any(|c| c.as_os_str() == "tests") {
+        return true;
+    }
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let mut parts = name.split('.');
+    let base = parts.next().unwrap_or_default();
+    let inner: Vec<&str> = parts.collect();
+    // the last part is the extension, `spec` in `foo.spec` is part of the name
+    let marked = inner
+        .split_last()
+        .is_some_and(|(_, middle)| middle.iter().any(|p| matches!(*p, "test" | "spec")));
+    marked
+        || matches!(base, "test" | "tests")
+        || base.starts_with("test_")
+        || base.ends_with("_test")
+        || base.ends_with("_tests")
+}
+
+fn uppercase_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn lowercase_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_repo::TestRepo;
+
+    const PLAIN: MessageStyle = MessageStyle {
+        conventional: false,
+        uwu: false,
+    };
+    const CONVENTIONAL: MessageStyle = MessageStyle {
+        conventional: true,
+        uwu: false,
+    };
+
+    fn change(kind: ChangeKind, path: &str) -> PathChange {
+        PathChange {
+            kind,
+            path: PathBuf::from(path),
+            old_path: None,
+        }
+    }
+
+    #[test]
+    fn single_file_is_the_summary() {
+        let changes = [change(ChangeKind::Modified, "src/git/mod.rs")];
+        assert_eq!(generate(&changes, PLAIN), "Update src/git/mod.rs");
+        assert_eq!(
+            generate(&changes, CONVENTIONAL),
+            "chore(git): update src/git/mod.rs"
+        );
+    }
+
+    #[test]
+    fn renames_name_both_paths() {
+        let changes = [PathChange {
+            kind: ChangeKind::Renamed,
+            path: PathBuf::from("src/new.rs"),
+            old_path: Some(PathBuf::from("src/old.rs")),
+        }];
+        assert_eq!(generate(&changes, PLAIN), "Rename src/old.rs to src/new.rs");
+    }
+
+    #[test]
+    fn mixed_kinds_are_updates_with_a_body() {
+        let changes = [
+            change(ChangeKind::Added, "src/tui/app.rs"),
+            change(ChangeKind::Deleted, "src/git/old.rs"),
+        ];
+        assert_eq!(
+            generate(&changes, PLAIN),
+            "Update 2 files in tui, git\n\n- add src/tui/app.rs\n- remove src/git/old.rs"
+        );
+    }
+
+    #[test]
+    fn same_kind_keeps_its_verb() {
+        let changes = [
+            change(ChangeKind::Added, "src/tui/app.rs"),
+            change(ChangeKind::Added, "src/tui/ui.rs"),
+        ];
+        assert!(generate(&changes, CONVENTIONAL).starts_with("feat(tui): add 2 files in tui\n"));
+    }
+
+    #[test]
+    fn many_modules_are_counted() {
+        let changes = [
+            change(ChangeKind::Modified, "src/git/mod.rs"),
+            change(ChangeKind::Modified, "src/tui/ui.rs"),
+            change(ChangeKind::Modified, "src/main.rs"),
+            change(ChangeKind::Modified, "README.md"),
+        ];
+        let message = generate(&changes, PLAIN);
+        assert_eq!(message.lines().next(), Some("Update 4 files in 4 modules"));
+    }
+
+    #[test]
+    fn long_bodies_are_cut() {
+        let changes: Vec<PathChange> = (0..MAX_BODY_FILES + 5)
+            .map(|i| change(ChangeKind::Added, &format!("data/{}.csv", i)))
+            .collect();
+        let message = generate(&changes, PLAIN);
+        assert!(message.ends_with("\n- ... and 5 more"));
+        assert_eq!(
+            message.lines().filter(|l| l.starts_with("- ")).count(),
+            MAX_BODY_FILES + 1
+        );
+    }
+
+    #[test]
+    fn uwu_signs_off() {
+        let style = MessageStyle {
+            conventional: false,
+            uwu: true,
+        };
+        let message = generate(&[change(ChangeKind::Added, "a.txt")], style);
+        assert!(message.ends_with(&format!("\n\n{}", uwu::get_commit_flavour())));
+    }
+
+    #[test]
+    fn modules_come_from_the_path() {
+        assert_eq!(module_of(Path::new("src/tui/ui.rs")), "tui");
+        assert_eq!(module_of(Path::new("src/main.rs")), "main");
+        assert_eq!(module_of(Path::new("docs/guide/x.md")), "docs");
+        assert_eq!(module_of(Path::new("Cargo.toml")), "Cargo");
+    }
+
+    #[test]
+    fn conventional_types_follow_the_paths() {
+        let kind = |paths: &[&str]| {
+            let changes: Vec<PathChange> = paths
+                .iter()
+                .map(|p| change(ChangeKind::Modified, p))
+                .collect();
+            conventional_type(&changes)
+        };
+        assert_eq!(kind(&["README.md", "docs/setup.txt"]), "docs");
+        assert_eq!(kind(&["Cargo.toml", "Cargo.lock"]), "build");
+        assert_eq!(kind(&["src/main.rs", "Cargo.toml"]), "chore");
+        assert_eq!(
+            kind(&[
+                "tests/cli.rs",
+                "crates/core/tests/it.rs",
+                "test_parser.py",
+                "parser_test.go",
+                "parser_tests.rs",
+                "web/app.test.ts",
+                "web/app.spec.js",
+            ]),
+            "test"
+        );
+        for not_a_test in [
+            "src/latest.rs",
+            "contest.rs",
+            "attestation.rs",
+            "web/app.spec",
+        ] {
+            assert_eq!(kind(&[not_a_test]), "chore", "{}", not_a_test);
+        }
+    }
+
+    #[test]
+    fn from_diff_sees_renames() {
+        let test = TestRepo::new();
+        test.write("old.txt", "some content that stays the same\n");
+        test.commit_all("first");
+        let repo = test.repo();
+        let parent = repo.head().unwrap().peel_to_tree().unwrap();
+        std::fs::rename(test.path().join("old.txt"), test.path().join("new.txt")).unwrap();
+        test.commit_all("second");
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+
+        let mut diff = repo
+            .diff_tree_to_tree(Some(&parent), Some(&tree), None)
+            .unwrap();
+        diff.find_similar(None).unwrap();
+        assert_eq!(from_diff(&diff, PLAIN), "Rename old.txt to new.txt");
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, bail, Result};
+use git2::{Branch, BranchType, Cred, CredentialType, PushOptions, RemoteCallbacks, Repository};
+use std::cell::RefCell;
+use std::path::Path;
+
+/// How the current branch compares to its upstream, as of the last fetch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracking {
+    /// `origin/main` and the like.
+    pub upstream: String,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// `None` when HEAD is detached, unborn or the branch has no upstream.
+pub fn tracking(repo_path: &Path) -> Result<Option<Tracking>> {
+    let repo = Repository::open(repo_path)?;
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head,
+        _ => return Ok(None),
+    };
+    let local = head.target().ok_or_else(|| anyhow!("HEAD has no target"))?;
+    let upstream = match Branch::wrap(head).upstream() {
+        Ok(upstream) => upstream,
+        Err(_) => return Ok(None),
+    };
+    let Some(upstream_id) = upstream.get().target() else {
+        return Ok(None);
+    };
+    let (ahead, behind) = repo.graph_ahead_behind(local, upstream_id)?;
+    Ok(Some(Tracking {
+        upstream: upstream.name()?.unwrap_or_default().to_string(),
+        ahead,
+        behind,
+    }))
+}
+
+/// Pushes the current branch to its upstream. A branch without one goes to
+/// `origin` under the same name and gets it set as its upstream, like
+/// `git push -u origin <branch>`. Returns where it was pushed to.
+pub fn push(repo_path: &Path) -> Result<String> {
+    let repo = Repository::open(repo_path)?;
+    let head = repo.head()?;
+    if !head.is_branch() {
+        bail!("HEAD is detached, there is no branch to push");
+    }
+    let ref_name = head.name().unwrap_or_default().to_string();
+    let branch_name = head.shorthand().unwrap_or_default().to_string();
+
+    let (remote_name, remote_ref, has_upstream) = match repo.branch_upstream_remote(&ref_name) {
+        Ok(remote) => {
+            let remote = remote.as_str().unwrap_or("origin").to_string();
+            let merge = repo.branch_upstream_merge(&ref_name)?;
+            let merge = merge.as_str().unwrap_or(&ref_name).to_string();
+            (remote, merge, true)
+        }
+        Err(_) => ("origin".to_string(), ref_name.clone(), false),
+    };
+
+    let mut remote = repo
+        .find_remote(&remote_name)
+        .map_err(|_| anyhow!("no upstream and no remote called {}", remote_name))?;
+
+    // the server can refuse a single ref even when the push as a whole works
+    let rejected = RefCell::new(None);
+    let config = repo.config()?;
+    let mut callbacks = credential_callbacks(&config);
+    callbacks.push_update_reference(|refname, status| {
+        if let Some(status) = status {
+            *rejected.borrow_mut() = Some(format!("{} was rejected: {}", refname, status));
+        }
+        Ok(())
+    });
+    let mut opts = PushOptions::new();
+    opts.remote_callbacks(callbacks);
+    remote.push(&[format!("{}:{}", ref_name, remote_ref)], Some(&mut opts))?;
+    drop(opts);
+    if let Some(reason) = rejected.into_inner() {
+        bail!(reason);
+    }
+
+    if !has_upstream {
+        let mut branch = repo.find_branch(&branch_name, BranchType::Local)?;
+        branch.set_upstream(Some(&format!("{}/{}", remote_name, branch_name)))?;
+    }
+    Ok(format!("{}/{}", remote_name, branch_name))
+}
+
+/// Tries the ssh agent for ssh remotes and git's credential helpers for https
+/// ones. libgit2 keeps asking as long as we keep answering, so every kind of
+/// credential only gets one go.
+fn credential_callbacks(config: &git2::Config) -> RemoteCallbacks<'_> {
+    let mut tried = CredentialType::empty();
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) && !tried.contains(CredentialType::SSH_KEY) {
+            tried |= CredentialType::SSH_KEY;
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT)
+            && !tried.contains(CredentialType::USER_PASS_PLAINTEXT)
+        {
+            tried |= CredentialType::USER_PASS_PLAINTEXT;
+            return Cred::credential_helper(config, url, username);
+        }
+        if allowed.contains(CredentialType::DEFAULT) && !tried.contains(CredentialType::DEFAULT) {
+            tried |= CredentialType::DEFAULT;
+            return Cred::default();
+        }
+        Err(git2::Error::from_str(
+            "no credentials worked, check your ssh agent or credential helper",
+        ))
+    });
+    callbacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_repo::TestRepo;
+
+    #[test]
+    fn push_catches_the_upstream_up() {
+        let remote = TestRepo::bare();
+        let local = TestRepo::new();
+        local
+            .repo()
+            .remote("origin", remote.path().to_str().unwrap())
+            .unwrap();
+
+        local.write("a.txt", "one\n");
+        let first = local.commit_all("first");
+        assert_eq!(tracking(local.path()).unwrap(), None);
+
+        assert_eq!(push(local.path()).unwrap(), "origin/main");
+        let upstream = |ahead, behind| {
+            Some(Tracking {
+                upstream: "origin/main".to_string(),
+                ahead,
+                behind,
+            })
+        };
+        assert_eq!(tracking(local.path()).unwrap(), upstream(0, 0));
+
+        local.write("a.txt", "two\n");
+        local.commit_all("second");
+        assert_eq!(tracking(local.path()).unwrap(), upstream(1, 0));
+        push(local.path()).unwrap();
+        assert_eq!(tracking(local.path()).unwrap(), upstream(0, 0));
+        assert_eq!(
+            remote.repo().refname_to_id("refs/heads/main").unwrap(),
+            local.repo().head().unwrap().target().unwrap()
+        );
+
+        let repo = local.repo();
+        let first = repo.find_object(first, None).unwrap();
+        repo.reset(&first, git2::ResetType::Soft, None).unwrap();
+        assert_eq!(tracking(local.path()).unwrap(), upstream(0, 1));
+    }
+}
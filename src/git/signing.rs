@@ -0,0 +1,199 @@
+use anyhow::{anyhow, bail, Context, Result};
+use git2::{Commit, Oid, Repository, Signature, Tree};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How `commit.gpgsign` commits get signed, read from the same config keys git
+/// uses.
+enum Signer {
+    OpenPgp {
+        program: String,
+        key: Option<String>,
+    },
+    Ssh {
+        program: String,
+        key: String,
+    },
+}
+
+impl Signer {
+    /// `None` unless `commit.gpgsign` is on.
+    fn from_config(repo: &Repository) -> Result<Option<Self>> {
+        let config = repo.config()?.snapshot()?;
+        if !config.get_bool("commit.gpgsign").unwrap_or(false) {
+            return Ok(None);
+        }
+        let key = config.get_string("user.signingkey").ok();
+        let format = config
+            .get_string("gpg.format")
+            .unwrap_or_else(|_| "openpgp".to_string());
+
+        match format.as_str() {
+            "openpgp" => Ok(Some(Signer::OpenPgp {
+                program: config
+                    .get_string("gpg.openpgp.program")
+                    .or_else(|_| config.get_string("gpg.program"))
+                    .unwrap_or_else(|_| "gpg".to_string()),
+                key,
+            })),
+            "ssh" => Ok(Some(Signer::Ssh {
+                program: config
+                    .get_string("gpg.ssh.program")
+                    .unwrap_or_else(|_| "ssh-keygen".to_string()),
+                key: key
+                    .ok_or_else(|| anyhow!("gpg.format is ssh but user.signingkey is not set"))?,
+            })),
+            other => bail!("signing with gpg.format {} is not supported", other),
+        }
+    }
+
+    fn sign(&self, buffer: &str, committer: &Signature) -> Result<String> {
+        match self {
+            Signer::OpenPgp { program, key } => {
+                // like git, fall back to the committer when no key is configured
+                let key = match key {
+                    Some(key) => key.clone(),
+                    None => committer.to_string(),
+                };
+                let mut child = Command::new(program)
+                    .args(["--status-fd=2", "-bsau", &key])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("couldn't run {}", program))?;
+                child
+                    .stdin
+                    .take()
+                    .ok_or_else(|| anyhow!("no stdin for {}", program))?
+                    .write_all(buffer.as_bytes())?;
+                let output = child.wait_with_output()?;
+                let status = String::from_utf8_lossy(&output.stderr);
+                if !output.status.success() || !status.contains("[GNUPG:] SIG_CREATED ") {
+                    bail!("{} failed to sign the commit\n{}", program, status.trim());
+                }
+                Ok(String::from_utf8(output.stdout)?)
+            }
+            Signer::Ssh { program, key } => {
+                let dir = PrivateDir::create()?;
+                let buffer_file = dir.write("commit", buffer)?;
+                let signature_file = dir.0.join("commit.sig");
+
+                // a literal public key means the private half lives in the agent
+                let literal = key.strip_prefix("key::").or_else(|| {
+                    (key.starts_with("ssh-") || key.starts_with("ecdsa-")).then_some(key.as_str())
+                });
+                let mut command = Command::new(program);
+                command.args(["-Y", "sign", "-n", "git", "-f"]);
+                match literal {
+                    Some(public_key) => {
+                        command
+                            .arg(dir.write("signing-key.pub", public_key)?)
+                            .arg("-U");
+                    }
+                    None => {
+                        command.arg(expand_home(key));
+                    }
+                };
+                let output = command
+                    .arg(&buffer_file)
+                    .stdin(Stdio::null())
+                    .output()
+                    .with_context(|| format!("couldn't run {}", program))?;
+                if !output.status.success() {
+                    bail!(
+                        "{} failed to sign the commit\n{}",
+                        program,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+                Ok(fs::read_to_string(&signature_file)?)
+            }
+        }
+    }
+}
+
+/// A fresh directory in the temp dir only we can read, for the files
+/// ssh-keygen works on. Other users on the machine can neither read the commit
+/// nor swap the files out through a name they guessed in advance.
+struct PrivateDir(PathBuf);
+
+impl PrivateDir {
+    fn create() -> Result<Self> {
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        for attempt in 0..100u32 {
+            let path = std::env::temp_dir().join(format!(
+                "kris-sign-{}-{}-{}",
+                std::process::id(),
+                nanos,
+                attempt
+            ));
+            match builder.create(&path) {
+                Ok(()) => return Ok(Self(path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).context("couldn't create a directory for signing"),
+            }
+        }
+        bail!("couldn't create a directory for signing")
+    }
+
+    /// Never follows or reuses something that is already there.
+    fn write(&self, name: &str, content: &str) -> Result<PathBuf> {
+        let path = self.0.join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&path)?.write_all(content.as_bytes())?;
+        Ok(path)
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Writes a commit object, signed when `commit.gpgsign` asks for it. No ref
+/// is moved, that's up to the caller. A signature that can't be made is an
+/// error, never an unsigned commit.
+pub fn create_commit(
+    repo: &Repository,
+    signature: &Signature,
+    message: &str,
+    tree: &Tree,
+    parents: &[&Commit],
+) -> Result<Oid> {
+    let Some(signer) = Signer::from_config(repo)? else {
+        return Ok(repo.commit(None, signature, signature, message, tree, parents)?);
+    };
+    let buffer = repo.commit_create_buffer(signature, signature, message, tree, parents)?;
+    let buffer = buffer
+        .as_str()
+        .ok_or_else(|| anyhow!("commit buffer is not valid UTF-8"))?;
+    let commit_signature = signer.sign(buffer, signature)?;
+    Ok(repo.commit_signed(buffer, &commit_signature, Some("gpgsig"))?)
+}
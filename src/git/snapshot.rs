@@ -0,0 +1,316 @@
+use crate::git::hunks::{self, Hunk};
+use crate::git::{for_each_patch, is_autosave_commit, ChangeCounts, ChangeKind};
+use anyhow::{anyhow, Result};
+use git2::{
+    Delta, Diff, DiffOptions, Index, IndexEntry, IndexTime, Oid, Patch, Repository, Status,
+    StatusOptions, StatusShow,
+};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How far back the timeline goes, per source.
+const MAX_SNAPSHOTS: usize = 100;
+
+/// A point the work tree can be restored to: either a commit on the snapshot
+/// ref or an `--autosave` commit on the branch itself.
+pub struct Snapshot {
+    pub id: Oid,
+    /// Seconds since the epoch.
+    pub time: i64,
+    pub summary: String,
+    /// What changed compared to the snapshot before it.
+    pub changes: ChangeCounts,
+    pub autosave: bool,
+}
+
+/// A file that differs between a snapshot and the work tree.
+pub struct SnapshotFile {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+/// Where the snapshots of a branch live. They are never pushed and never show
+/// up in `git log` unless asked for.
+pub const SNAPSHOT_REF_PREFIX: &str = "refs/kris/snapshots/";
+
+/// Name of the branch HEAD points at, even when it has no commits yet. A
+/// detached HEAD gets its own `detached` snapshot ref.
+pub fn branch_name(repo: &Repository) -> Result<String> {
+    let head = repo.find_reference("HEAD")?;
+    Ok(match head.symbolic_target() {
+        Some(target) => target.trim_start_matches("refs/heads/").to_string(),
+        None => "detached".to_string(),
+    })
+}
+
+pub fn snapshot_ref(branch: &str) -> String {
+    format!("{}{}", SNAPSHOT_REF_PREFIX, branch)
+}
+
+/// Records the work tree, untracked files included, as a commit on
+/// `refs/kris/snapshots/<branch>`. The tree is built in a throwaway in-memory
+/// index so the real index, HEAD and the files on disk are never written.
+/// Returns `None` when nothing changed since the last snapshot, or since HEAD
+/// for the first one.
+pub fn take_snapshot(repo_path: &Path) -> Result<Option<(String, Oid)>> {
+    let repo = Repository::open(repo_path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("bare repositories have no work tree"))?
+        .to_path_buf();
+
+    let head_commit = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let mut index = Index::new()?;
+    if let Some(commit) = &head_commit {
+        index.read_tree(&commit.tree()?)?;
+    }
+
+    let mut opts = StatusOptions::new();
+    opts.show(StatusShow::IndexAndWorkdir)
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    for entry in repo.statuses(Some(&mut opts))?.iter() {
+        if entry.status().contains(Status::IGNORED) {
+            continue;
+        }
+        let Some(path) = entry.path() else {
+            continue;
+        };
+        let full_path = workdir.join(path);
+        match fs::symlink_metadata(&full_path) {
+            Ok(meta) if meta.is_dir() => {}
+            Ok(meta) => index.add(&workdir_entry(&repo, path, &full_path, &meta)?)?,
+            Err(_) => {
+                let _ = index.remove_path(Path::new(path));
+            }
+        }
+    }
+    let tree = repo.find_tree(index.write_tree_to(&repo)?)?;
+
+    let branch = branch_name(&repo)?;
+    let ref_name = snapshot_ref(&branch);
+    let last_snapshot = repo
+        .find_reference(&ref_name)
+        .ok()
+        .and_then(|r| r.peel_to_commit().ok());
+    let unchanged_since = match (&last_snapshot, &head_commit) {
+        (Some(snapshot), _) => Some(snapshot.tree_id()),
+        (None, Some(head)) => Some(head.tree_id()),
+        (None, None) => None,
+    };
+    if unchanged_since == Some(tree.id()) || (unchanged_since.is_none() && tree.is_empty()) {
+        return Ok(None);
+    }
+
+    let base = match &head_commit {
+        Some(commit) => commit.id().to_string()[..7].to_string(),
+        None => "no commits yet".to_string(),
+    };
+    let message = format!("kris snapshot of {} ({})", branch, base);
+    let signature = repo.signature()?;
+    let parents: Vec<&git2::Commit> = last_snapshot.iter().collect();
+    let oid = repo.commit(None, &signature, &signature, &message, &tree, &parents)?;
+    repo.reference(&ref_name, oid, true, "kris snapshot")?;
+    Ok(Some((ref_name, oid)))
+}
+
+/// Index entry for a file as it is on disk, with its blob written to the odb.
+fn workdir_entry(
+    repo: &Repository,
+    path: &str,
+    full_path: &Path,
+    meta: &fs::Metadata,
+) -> Result<IndexEntry> {
+    let (id, mode) = if meta.file_type().is_symlink() {
+        let target = fs::read_link(full_path)?;
+        let id = repo.blob(target.to_string_lossy().as_bytes())?;
+        (id, 0o120000)
+    } else {
+        (repo.blob_path(full_path)?, file_mode(meta))
+    };
+    Ok(IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode,
+        uid: 0,
+        gid: 0,
+        file_size: meta.len() as u32,
+        id,
+        flags: 0,
+        flags_extended: 0,
+        path: path.as_bytes().to_vec(),
+    })
+}
+
+#[cfg(unix)]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if meta.permissions().mode() & 0o111 != 0 {
+        0o100755
+    } else {
+        0o100644
+    }
+}
+
+#[cfg(not(unix))]
+fn file_mode(_meta: &fs::Metadata) -> u32 {
+    0o100644
+}
+
+/// Snapshots of the current branch and the autosave commits on it, newest first.
+pub fn list_snapshots(repo_path: &Path) -> Result<Vec<Snapshot>> {
+    let repo = Repository::open(repo_path)?;
+    let mut snapshots = Vec::new();
+
+    let ref_name = snapshot_ref(&branch_name(&repo)?);
+    if let Ok(reference) = repo.find_reference(&ref_name) {
+        let mut commit = reference.peel_to_commit().ok();
+        while let Some(current) = commit {
+            if snapshots.len() >= MAX_SNAPSHOTS {
+                break;
+            }
+            snapshots.push(describe(&repo, &current, false)?);
+            commit = current.parent(0).ok();
+        }
+    }
+
+    if let Ok(head) = repo.head() {
+        let mut walk = repo.revwalk()?;
+        walk.push(head.peel_to_commit()?.id())?;
+        walk.simplify_first_parent()?;
+        for id in walk.take(MAX_SNAPSHOTS) {
+            let commit = repo.find_commit(id?)?;
+            if is_autosave_commit(&commit) {
+                snapshots.push(describe(&repo, &commit, true)?);
+            }
+        }
+    }
+
+    snapshots.sort_by_key(|s| Reverse(s.time));
+    Ok(snapshots)
+}
+
+fn describe(repo: &Repository, commit: &git2::Commit, autosave: bool) -> Result<Snapshot> {
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    let mut changes = ChangeCounts::default();
+    for_each_patch(&diff, |_, _, insertions, deletions| {
+        changes.add(insertions, deletions)
+    })?;
+    Ok(Snapshot {
+        id: commit.id(),
+        time: commit.time().seconds(),
+        summary: commit.summary().unwrap_or_default().to_string(),
+        changes,
+        autosave,
+    })
+}
+
+/// Snapshot -> work tree, so `+` lines are what the work tree has on top of
+/// the snapshot. Ignored files are left out, restoring never touches them.
+fn workdir_diff<'a>(repo: &'a Repository, id: Oid, path: Option<&Path>) -> Result<Diff<'a>> {
+    let tree = repo.find_commit(id)?.tree()?;
+    let mut opts = DiffOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    if let Some(path) = path {
+        opts.pathspec(path).disable_pathspec_match(true);
+    }
+    Ok(repo.diff_tree_to_workdir(Some(&tree), Some(&mut opts))?)
+}
+
+pub fn snapshot_files(repo_path: &Path, id: Oid) -> Result<Vec<SnapshotFile>> {
+    let repo = Repository::open(repo_path)?;
+    let diff = workdir_diff(&repo, id, None)?;
+    let mut files = Vec::new();
+    for_each_patch(&diff, |path, status, insertions, deletions| {
+        files.push(SnapshotFile {
+            path,
+            // only new compared to the snapshot, git may well know it already
+            kind: match status {
+                Delta::Untracked => ChangeKind::Added,
+                status => ChangeKind::from_delta(status),
+            },
+            insertions: insertions as u32,
+            deletions: deletions as u32,
+        })
+    })?;
+    Ok(files)
+}
+
+pub fn snapshot_file_hunks(repo_path: &Path, id: Oid, path: &Path) -> Result<Vec<Hunk>> {
+    let repo = Repository::open(repo_path)?;
+    let diff = workdir_diff(&repo, id, Some(path))?;
+    let patch = Patch::from_diff(&diff, 0)?;
+    match patch {
+        Some(patch) => hunks::from_patch(&patch),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Puts `path` (or every file that differs, with `None`) back the way it was
+/// in the snapshot. The current state is snapshotted first so a restore can be
+/// undone from the timeline too. Only the work tree is written, the index and
+/// HEAD stay as they are.
+pub fn restore(repo_path: &Path, id: Oid, path: Option<&Path>) -> Result<usize> {
+    take_snapshot(repo_path)?;
+
+    let repo = Repository::open(repo_path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("bare repositories have no work tree"))?
+        .to_path_buf();
+    let tree = repo.find_commit(id)?.tree()?;
+    let diff = workdir_diff(&repo, id, path)?;
+
+    let mut restored = 0;
+    for delta in diff.deltas() {
+        let Some(path) = delta.old_file().path().or_else(|| delta.new_file().path()) else {
+            continue;
+        };
+        let full_path = workdir.join(path);
+        match tree.get_path(path) {
+            Ok(entry) => {
+                let blob = repo.find_blob(entry.id())?;
+                write_file(&full_path, blob.content(), entry.filemode() as u32)?;
+            }
+            // wasn't there when the snapshot was taken
+            Err(_) => fs::remove_file(&full_path)?,
+        }
+        restored += 1;
+    }
+    Ok(restored)
+}
+
+fn write_file(path: &Path, content: &[u8], mode: u32) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    #[cfg(unix)]
+    if mode == 0o120000 {
+        let target = String::from_utf8_lossy(content).into_owned();
+        std::os::unix::fs::symlink(target, path)?;
+        return Ok(());
+    }
+    fs::write(path, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let perms = if mode == 0o100755 { 0o755 } else { 0o644 };
+        fs::set_permissions(path, fs::Permissions::from_mode(perms))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use crate::git::message::{self, MessageStyle};
+use crate::git::protect::{self, BranchProtection};
+use crate::git::{is_autosave_commit, is_pushed, signing, state, REFLOG_PREFIX};
+use anyhow::{bail, Result};
+use git2::{Oid, Repository};
+use std::path::Path;
+
+/// The autosave commits at the tip of the current branch, ready to become one.
+pub struct SquashPlan {
+    pub count: usize,
+    /// Parent of the oldest autosave commit, `None` if the run starts at the root.
+    base: Option<Oid>,
+    head: Oid,
+    /// Generated from everything the run changed together.
+    pub message: String,
+}
+
+/// Finds the trailing run of `--autosave` commits and refuses when there is
+/// nothing to squash, when any of them already made it to a remote or
+/// when the branch is protected.
+pub fn plan(
+    repo_path: &Path,
+    style: MessageStyle,
+    protection: &BranchProtection,
+) -> Result<SquashPlan> {
+    if let Some(blocker) = state::commit_blocker(repo_path)? {
+        bail!("{}", blocker);
+    }
+    let repo = Repository::open(repo_path)?;
+    protect::check_rewrite(&repo, protection)?;
+    let head = repo.head()?.peel_to_commit()?;
+
+    let mut count = 0;
+    let mut base = None;
+    let mut commit = Some(head.clone());
+    while let Some(current) = commit {
+        if !is_autosave_commit(&current) {
+            base = Some(current);
+            break;
+        }
+        if is_pushed(&repo, current.id())? {
+            bail!(
+                "{} is already pushed, squashing would rewrite shared history",
+                &current.id().to_string()[..7]
+            );
+        }
+        count += 1;
+        commit = current.parent(0).ok();
+    }
+    match count {
+        0 => bail!("there are no autosave commits at the tip of this branch"),
+        1 => bail!("there is only one autosave commit, nothing to squash"),
+        _ => {}
+    }
+
+    let base_tree = base.as_ref().map(|c| c.tree()).transpose()?;
+    let mut diff = repo.diff_tree_to_tree(base_tree.as_ref(), Some(&head.tree()?), None)?;
+    diff.find_similar(None)?;
+
+    Ok(SquashPlan {
+        count,
+        base: base.map(|c| c.id()),
+        head: head.id(),
+        message: message::from_diff(&diff, style),
+    })
+}
+
+/// Replaces the run with a single commit holding the same tree. Only the
+/// branch moves, the index and the work tree already match.
+pub fn squash(
+    repo_path: &Path,
+    plan: &SquashPlan,
+    message: &str,
+    protection: &BranchProtection,
+) -> Result<Oid> {
+    let repo = Repository::open(repo_path)?;
+    // the branch could have been switched while the message was edited
+    protect::check_rewrite(&repo, protection)?;
+    let mut head = repo.head()?;
+    if head.target() != Some(plan.head) {
+        bail!("the branch moved since the squash was planned, try again");
+    }
+
+    let tree = repo.find_commit(plan.head)?.tree()?;
+    let base = plan.base.map(|id| repo.find_commit(id)).transpose()?;
+    let parents: Vec<&git2::Commit> = base.iter().collect();
+    let signature = repo.signature()?;
+    let oid = signing::create_commit(&repo, &signature, message, &tree, &parents)?;
+    head.set_target(
+        oid,
+        &format!("{}squash {} autosave commits", REFLOG_PREFIX, plan.count),
+    )?;
+    Ok(oid)
+}
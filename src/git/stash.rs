@@ -0,0 +1,72 @@
+use crate::git::message::MessageStyle;
+use crate::git::suggest_message;
+use anyhow::Result;
+use git2::{Repository, StashApplyOptions, StashFlags};
+use std::path::Path;
+
+pub struct StashEntry {
+    /// Position on the stash stack, `stash@{index}`.
+    pub index: usize,
+    pub message: String,
+    /// Seconds since the epoch.
+    pub time: i64,
+}
+
+/// Shelves everything, untracked files included, under a message generated
+/// the same way as a commit message. Returns the summary used.
+pub fn save(repo_path: &Path, style: MessageStyle) -> Result<String> {
+    // the signature line would just be noise in a one-line stash message
+    let style = MessageStyle {
+        uwu: false,
+        ..style
+    };
+    let summary = suggest_message(repo_path, style, None)?
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut repo = Repository::open(repo_path)?;
+    let signature = repo.signature()?;
+    repo.stash_save(
+        &signature,
+        &format!("kris: {}", summary),
+        Some(StashFlags::INCLUDE_UNTRACKED),
+    )?;
+    Ok(summary)
+}
+
+/// Newest first, like `git stash list`.
+pub fn list(repo_path: &Path) -> Result<Vec<StashEntry>> {
+    let mut repo = Repository::open(repo_path)?;
+    let mut stashes = Vec::new();
+    repo.stash_foreach(|index, message, id| {
+        stashes.push((index, message.to_string(), *id));
+        true
+    })?;
+
+    let mut entries = Vec::with_capacity(stashes.len());
+    for (index, message, id) in stashes {
+        entries.push(StashEntry {
+            index,
+            message,
+            time: repo.find_commit(id)?.time().seconds(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Applies `stash@{index}` on top of the work tree and keeps it on the stack.
+/// Untracked files that were stashed come back as untracked files.
+pub fn apply(repo_path: &Path, index: usize) -> Result<()> {
+    let mut repo = Repository::open(repo_path)?;
+    let mut opts = StashApplyOptions::new();
+    repo.stash_apply(index, Some(&mut opts))?;
+    Ok(())
+}
+
+pub fn drop(repo_path: &Path, index: usize) -> Result<()> {
+    let mut repo = Repository::open(repo_path)?;
+    repo.stash_drop(index)?;
+    Ok(())
+}
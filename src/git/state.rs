@@ -0,0 +1,101 @@
+use anyhow::Result;
+use git2::{Repository, RepositoryState};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Something git started and the user has to finish. Committing in the middle
+/// of one makes a mess, so kris doesn't.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+    /// `git am`
+    ApplyMailbox,
+}
+
+impl Operation {
+    fn from_state(state: RepositoryState) -> Option<Self> {
+        match state {
+            RepositoryState::Clean => None,
+            RepositoryState::Merge => Some(Operation::Merge),
+            RepositoryState::Revert | RepositoryState::RevertSequence => Some(Operation::Revert),
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+                Some(Operation::CherryPick)
+            }
+            RepositoryState::Bisect => Some(Operation::Bisect),
+            RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge => Some(Operation::Rebase),
+            RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => {
+                Some(Operation::ApplyMailbox)
+            }
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Merge => "merge",
+            Operation::Rebase => "rebase",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+            Operation::Bisect => "bisect",
+            Operation::ApplyMailbox => "git am",
+        }
+    }
+}
+
+/// Why kris won't commit right now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blocker {
+    pub operation: Option<Operation>,
+    /// Files the index still has conflict entries for.
+    pub conflicts: Vec<PathBuf>,
+}
+
+impl fmt::Display for Blocker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.operation {
+            Some(operation) => write!(f, "finish your {} first", operation.name())?,
+            None => write!(f, "resolve the conflicts first")?,
+        }
+        if !self.conflicts.is_empty() {
+            write!(f, ", still conflicted:")?;
+            for path in &self.conflicts {
+                write!(f, "\n  {}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `None` when committing is safe: no merge, rebase, cherry-pick, revert,
+/// bisect or `git am` in progress and no conflicts left in the index.
+pub fn commit_blocker(repo_path: &Path) -> Result<Option<Blocker>> {
+    let repo = Repository::open(repo_path)?;
+    let operation = Operation::from_state(repo.state());
+
+    let mut conflicts = Vec::new();
+    let index = repo.index()?;
+    if index.has_conflicts() {
+        for conflict in index.conflicts()? {
+            let conflict = conflict?;
+            let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+            if let Some(entry) = entry {
+                conflicts.push(PathBuf::from(
+                    String::from_utf8_lossy(&entry.path).into_owned(),
+                ));
+            }
+        }
+    }
+
+    if operation.is_none() && conflicts.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Blocker {
+        operation,
+        conflicts,
+    }))
+}
@@ -0,0 +1,115 @@
+use crate::git::message::MessageStyle;
+use crate::git::protect::{BranchProtection, ProtectedAction};
+use crate::git::CommitRequest;
+use git2::{Oid, Repository, RepositoryInitOptions, Signature};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn unprotected() -> BranchProtection {
+    BranchProtection {
+        patterns: Vec::new(),
+        action: ProtectedAction::Refuse,
+    }
+}
+
+/// Commits everything with a fixed message, like a popup `Yes` without picking.
+pub fn request(message: &str) -> CommitRequest {
+    CommitRequest {
+        message: Some(message.to_string()),
+        style: MessageStyle {
+            conventional: false,
+            uwu: false,
+        },
+        paths: None,
+        hunks: HashMap::new(),
+        autosave: false,
+        limits: None,
+        submodules: false,
+    }
+}
+
+/// A throwaway repo under the temp dir, removed again on drop.
+pub struct TestRepo {
+    path: PathBuf,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        Self::init(false)
+    }
+
+    pub fn bare() -> Self {
+        Self::init(true)
+    }
+
+    fn init(bare: bool) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "kris-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        let mut opts = RepositoryInitOptions::new();
+        opts.bare(bare).initial_head("main");
+        let repo = Repository::init_opts(&path, &opts).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test User").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        Self { path }
+    }
+
+    pub fn repo(&self) -> Repository {
+        Repository::open(&self.path).unwrap()
+    }
+
+    pub fn write(&self, name: &str, content: &str) {
+        let path = self.path.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, content).unwrap();
+    }
+
+    /// Stages everything and commits it on HEAD, the way a user would.
+    pub fn commit_all(&self, message: &str) -> Oid {
+        let repo = self.repo();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::now("Test User", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    /// Clones `sub` into `path` as a submodule, staged but not committed.
+    pub fn add_submodule(&self, sub: &TestRepo, path: &str) {
+        let repo = self.repo();
+        let mut submodule = repo
+            .submodule(sub.path().to_str().unwrap(), Path::new(path), true)
+            .unwrap();
+        let clone = submodule.clone(None).unwrap();
+        let mut config = clone.config().unwrap();
+        config.set_str("user.name", "Test User").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        submodule.add_finalize().unwrap();
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
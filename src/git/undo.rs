@@ -0,0 +1,150 @@
+use crate::git::{is_autosave_commit, is_pushed, state, REFLOG_PREFIX};
+use anyhow::{bail, Result};
+use git2::{Oid, Repository, ResetType};
+use std::path::Path;
+
+/// The commit HEAD points at, checked and ready to be taken back.
+pub struct UndoPlan {
+    head: Oid,
+    pub short_id: String,
+    pub summary: String,
+}
+
+/// Only a commit kris made itself, with one parent and not pushed yet, can
+/// be undone. Everything else is the user's history to rewrite.
+pub fn plan(repo_path: &Path) -> Result<UndoPlan> {
+    if let Some(blocker) = state::commit_blocker(repo_path)? {
+        bail!("{}", blocker);
+    }
+    let repo = Repository::open(repo_path)?;
+    let head = repo.head()?;
+    let commit = head.peel_to_commit()?;
+    let short_id = commit.id().to_string()[..7].to_string();
+
+    match commit.parent_count() {
+        0 => bail!(
+            "{} is the first commit, there's nothing before it",
+            short_id
+        ),
+        1 => {}
+        _ => bail!("{} is a merge, undo that with git", short_id),
+    }
+    if !made_by_kris(&repo, head.name().unwrap_or("HEAD"), commit.id())?
+        && !is_autosave_commit(&commit)
+    {
+        bail!("{} wasn't made by kris", short_id);
+    }
+    if is_pushed(&repo, commit.id())? {
+        bail!(
+            "{} is already pushed, undoing it would rewrite shared history",
+            short_id
+        );
+    }
+
+    Ok(UndoPlan {
+        head: commit.id(),
+        short_id,
+        summary: commit.summary().unwrap_or_default().to_string(),
+    })
+}
+
+/// The last update of `ref_name` put `id` there and came from kris.
+fn made_by_kris(repo: &Repository, ref_name: &str, id: Oid) -> Result<bool> {
+    let reflog = repo.reflog(ref_name)?;
+    Ok(reflog.get(0).is_some_and(|entry| {
+        entry.id_new() == id
+            && entry
+                .message()
+                .is_some_and(|m| m.starts_with(REFLOG_PREFIX))
+    }))
+}
+
+/// `git reset --soft HEAD~1`: the branch goes back to the parent, the index
+/// keeps what was committed so the changes are staged again, and the work
+/// tree isn't touched.
+pub fn undo(repo_path: &Path, plan: &UndoPlan) -> Result<()> {
+    let repo = Repository::open(repo_path)?;
+    let commit = repo.head()?.peel_to_commit()?;
+    if commit.id() != plan.head {
+        bail!("HEAD moved since, try again");
+    }
+    let parent = commit.parent(0)?;
+    repo.reset(parent.as_object(), ResetType::Soft, None)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::perform_commit;
+    use crate::git::test_repo::{request, unprotected, TestRepo};
+    use crate::tui::notifications::NotificationManager;
+    use std::fs;
+
+    fn kris_commit(test: &TestRepo) -> Oid {
+        perform_commit(
+            test.path(),
+            &request("kris was here"),
+            &unprotected(),
+            &mut NotificationManager::new(10),
+        )
+        .unwrap();
+        test.repo().head().unwrap().target().unwrap()
+    }
+
+    #[test]
+    fn undo_keeps_the_changes_staged() {
+        let test = TestRepo::new();
+        test.write("a.txt", "one\n");
+        let first = test.commit_all("first");
+        test.write("a.txt", "two\n");
+        test.write("b.txt", "new\n");
+        let commit = kris_commit(&test);
+
+        let plan = plan(test.path()).unwrap();
+        assert_eq!(plan.summary, "kris was here");
+        undo(test.path(), &plan).unwrap();
+
+        let repo = test.repo();
+        assert_eq!(repo.head().unwrap().target(), Some(first));
+        // the index still holds exactly what was committed
+        let index_tree = repo.index().unwrap().write_tree().unwrap();
+        assert_eq!(index_tree, repo.find_commit(commit).unwrap().tree_id());
+        assert_eq!(
+            fs::read_to_string(test.path().join("a.txt")).unwrap(),
+            "two\n"
+        );
+        assert_eq!(
+            fs::read_to_string(test.path().join("b.txt")).unwrap(),
+            "new\n"
+        );
+    }
+
+    #[test]
+    fn refuses_the_users_own_commit() {
+        let test = TestRepo::new();
+        test.write("a.txt", "one\n");
+        test.commit_all("first");
+        test.write("a.txt", "two\n");
+        test.commit_all("mine");
+
+        let err = plan(test.path()).err().unwrap();
+        assert!(err.to_string().contains("wasn't made by kris"), "{}", err);
+    }
+
+    #[test]
+    fn refuses_a_commit_on_any_remote_branch() {
+        let test = TestRepo::new();
+        test.write("a.txt", "one\n");
+        test.commit_all("first");
+        test.write("a.txt", "two\n");
+        let commit = kris_commit(&test);
+        // pushed somewhere that isn't the upstream, which there is none of
+        test.repo()
+            .reference("refs/remotes/fork/experiment", commit, false, "fetch")
+            .unwrap();
+
+        let err = plan(test.path()).err().unwrap();
+        assert!(err.to_string().contains("already pushed"), "{}", err);
+    }
+}
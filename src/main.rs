@@ -1,4 +1,6 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::path::PathBuf;
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
@@ -7,62 +9,206 @@ mod config;
 mod git;
 mod tui;
 mod uwu;
+mod watcher;
 
-use config::Config;
+use config::{Command, Config};
+use git::{CommitOutcome, CommitRequest};
 use tui::{
     animation,
-    events::{self, AnimationResult, PopupSelection},
+    app::{App, RepoView},
+    events::{self, AnimationResult, LargeFileAction, StashAction},
     notifications::NotificationManager,
     terminal, ui,
 };
+use watcher::{WatcherCommand, WatcherEvent, WatcherSettings};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    show_prank()?; // >:3c
-
     let config = Config::new().map_err(|e| e.to_string())?;
+    if let Some(Command::Squash { message }) = &config.command {
+        return run_squash(&config, message.as_deref());
+    }
 
-    if git::is_in_git_repo() {
-        run_app(config)?;
-    } else {
+    show_prank()?; // >:3c
+
+    let repo_paths = find_repos(&config);
+    if repo_paths.is_empty() {
         ui::display_nothing_bruh()?;
+    } else {
+        run_app(config, repo_paths)?;
     }
 
     Ok(())
 }
 
-fn run_app(config: Config) -> Result<(), Box<dyn Error>> {
-    let (show_popup_tx, show_popup_rx) = mpsc::channel();
-    let (reset_timer_tx, reset_timer_rx) = mpsc::channel();
-
-    let loop_delay = config.loop_delay;
-    let audio_alert_mode = config.audio_alert_mode;
-    let shiggy_mode = config.shiggy_mode;
-    thread::spawn(move || {
-        git::git_watcher_loop(
-            show_popup_tx,
-            reset_timer_rx,
-            loop_delay,
-            audio_alert_mode,
-            shiggy_mode,
-        );
-    });
+/// `kris-watcher squash`: folds the trailing autosave commits of every watched
+/// repo into one commit, with the given message or a generated one.
+fn run_squash(config: &Config, message: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let repo_paths = find_repos(config);
+    if repo_paths.is_empty() {
+        return Err("no git repository here".into());
+    }
+    for repo_path in repo_paths {
+        let name = git::repo_name(&repo_path);
+        let result = git::squash::plan(&repo_path, config.message_style, &config.protection)
+            .and_then(|plan| {
+                let message = message.unwrap_or(&plan.message);
+                git::squash::squash(&repo_path, &plan, message, &config.protection)
+                    .map(|id| (plan.count, id))
+            });
+        match result {
+            Ok((count, id)) => println!(
+                "{}: squashed {} autosave commits into {}",
+                name,
+                count,
+                &id.to_string()[..7]
+            ),
+            Err(e) => eprintln!("{}: not squashing, {}", name, e),
+        }
+    }
+    Ok(())
+}
+
+fn find_repos(config: &Config) -> Vec<PathBuf> {
+    let mut repo_paths: Vec<PathBuf> = config
+        .repo_paths
+        .iter()
+        .filter_map(|path| git::find_repo_root(path))
+        .collect();
+    if let Some(scan_dir) = &config.scan_dir {
+        repo_paths.extend(git::scan_for_repos(scan_dir));
+    }
+
+    let mut seen = HashSet::new();
+    repo_paths.retain(|path| seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone())));
+    repo_paths
+}
+
+fn run_app(config: Config, repo_paths: Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let (events_tx, events_rx) = mpsc::channel();
+
+    let mut repos = Vec::new();
+    for (repo, repo_path) in repo_paths.into_iter().enumerate() {
+        let (commands_tx, commands_rx) = mpsc::channel();
+        repos.push(RepoView::new(repo_path.clone(), commands_tx));
+
+        let events_tx = events_tx.clone();
+        let settings = WatcherSettings {
+            loop_delay: config.loop_delay,
+            poll_interval: config.poll_interval,
+            audio_alert_mode: config.audio_alert_mode,
+            fs_events: config.fs_events,
+        };
+        thread::spawn(move || {
+            watcher::git_watcher_loop(repo, repo_path, events_tx, commands_rx, settings);
+        });
+    }
 
     let mut terminal = terminal::setup_terminal()?;
     let mut notification_manager = NotificationManager::new(5);
+    let mut app = App::new(repos, config.message_style, config.protection.clone());
 
     let mut frame_index = 0;
     let frame_duration = Duration::from_millis(20);
-    let mut show_popup = false;
-    let mut popup_selection = PopupSelection::Yes;
 
     loop {
-        if show_popup_rx.try_recv().is_ok() {
-            if config.autosave_mode {
-                notification_manager.add_notif("Auto-committing changes...".to_string());
-                git::perform_commit(&mut notification_manager)?;
-                reset_timer_tx.send(()).ok();
-            } else {
-                show_popup = true;
+        while let Ok(event) = events_rx.try_recv() {
+            match event {
+                WatcherEvent::Stats {
+                    repo,
+                    status,
+                    stats,
+                    files,
+                    tracking,
+                    blocker,
+                } => {
+                    app.repos[repo].status = status;
+                    app.repos[repo].stats = stats;
+                    app.repos[repo].files = files;
+                    if tracking.as_ref().map_or(0, |t| t.ahead) == 0 {
+                        app.repos[repo].needs_push = false;
+                    }
+                    app.repos[repo].tracking = tracking;
+                    app.repos[repo].blocker = blocker;
+                    if repo == app.selected_repo {
+                        app.refresh_history();
+                    }
+                }
+                WatcherEvent::NeedsCommit { repo } => {
+                    if config.snapshot_mode {
+                        snapshot_repo(&app.repos[repo], &mut notification_manager);
+                        app.repos[repo]
+                            .commands_tx
+                            .send(WatcherCommand::Committed)
+                            .ok();
+                    } else if let (true, Some(blocker)) =
+                        (config.autosave_mode, &app.repos[repo].blocker)
+                    {
+                        notification_manager.add_notif(format!(
+                            "Not auto-committing {}: {}",
+                            app.repos[repo].name, blocker
+                        ));
+                        app.repos[repo]
+                            .commands_tx
+                            .send(WatcherCommand::Snooze)
+                            .ok();
+                    } else if config.autosave_mode {
+                        notification_manager.add_notif(format!(
+                            "Auto-committing changes in {}...",
+                            app.repos[repo].name
+                        ));
+                        let request = CommitRequest {
+                            message: None,
+                            style: config.message_style,
+                            paths: None,
+                            hunks: HashMap::new(),
+                            autosave: true,
+                            limits: Some(config.file_limits),
+                            submodules: config.submodules,
+                        };
+                        let outcome = commit_repo(
+                            &app.repos[repo],
+                            &request,
+                            &config,
+                            &mut notification_manager,
+                        );
+                        let commands_tx = &app.repos[repo].commands_tx;
+                        match outcome {
+                            Some(CommitOutcome::Committed(skipped)) => {
+                                commands_tx.send(WatcherCommand::Committed).ok();
+                                app.open_large_files(repo, skipped);
+                                if config.push_mode {
+                                    app.push(repo);
+                                }
+                            }
+                            // nothing, or only the skipped files, was left, ask again later
+                            Some(CommitOutcome::NothingCommitted(skipped)) => {
+                                commands_tx.send(WatcherCommand::Snooze).ok();
+                                app.open_large_files(repo, skipped);
+                            }
+                            None => {
+                                commands_tx.send(WatcherCommand::Committed).ok();
+                            }
+                        }
+                    } else {
+                        app.request_commit(repo);
+                    }
+                }
+                WatcherEvent::NeedsPush { repo } => {
+                    app.repos[repo].needs_push = true;
+                    // a push after commit that failed earlier gets another go
+                    if config.push_mode {
+                        app.push(repo);
+                    }
+                }
+                WatcherEvent::Pushed { repo, result } => match result {
+                    Ok(upstream) => {
+                        app.repos[repo].needs_push = false;
+                        notification_manager
+                            .add_notif(format!("Pushed {} to {}", app.repos[repo].name, upstream));
+                    }
+                    Err(e) => notification_manager
+                        .add_notif(format!("Push in {} failed: {}", app.repos[repo].name, e)),
+                },
             }
         }
 
@@ -72,27 +218,114 @@ fn run_app(config: Config) -> Result<(), Box<dyn Error>> {
             ui::draw_ui(
                 f,
                 frame_index,
-                show_popup,
-                &popup_selection,
+                &app,
                 notification_manager.get_notifications(),
                 config.loop_delay,
                 config.shiggy_mode,
             );
         })?;
 
-        if let Some(result) =
-            events::handle_events(&mut show_popup, &mut popup_selection, &reset_timer_tx)?
-        {
+        if let Some(result) = events::handle_events(&mut app)? {
             match result {
-                AnimationResult::Commit => {
-                    git::perform_commit(&mut notification_manager)?;
-                    show_popup = false;
-                    reset_timer_tx.send(()).ok();
+                AnimationResult::Commit(repo, request) => {
+                    let committed = matches!(
+                        commit_repo(
+                            &app.repos[repo],
+                            &request,
+                            &config,
+                            &mut notification_manager
+                        ),
+                        Some(CommitOutcome::Committed(_))
+                    );
+                    app.close_popup(repo, WatcherCommand::Committed);
+                    if committed && config.push_mode {
+                        app.push(repo);
+                    }
+                }
+                AnimationResult::Restore(repo, id, path) => {
+                    let repo_view = &app.repos[repo];
+                    match git::snapshot::restore(&repo_view.path, id, path.as_deref()) {
+                        Ok(count) => notification_manager.add_notif(format!(
+                            "Restored {} file(s) in {} from {}",
+                            count,
+                            repo_view.name,
+                            &id.to_string()[..7]
+                        )),
+                        Err(e) => notification_manager
+                            .add_notif(format!("Restore in {} failed: {}", repo_view.name, e)),
+                    }
+                    if let Some(timeline) = &mut app.timeline {
+                        timeline.refresh(&app.repos[repo].path);
+                    }
+                }
+                AnimationResult::Squash(repo, plan, message) => {
+                    let repo_view = &app.repos[repo];
+                    match git::squash::squash(&repo_view.path, &plan, &message, &config.protection)
+                    {
+                        Ok(_) => notification_manager.add_notif(format!(
+                            "Squashed {} autosave commits in {}",
+                            plan.count, repo_view.name
+                        )),
+                        Err(e) => notification_manager
+                            .add_notif(format!("Squash in {} failed: {}", repo_view.name, e)),
+                    }
+                }
+                AnimationResult::Stash(repo, action) => {
+                    stash_repo(&app.repos[repo], action, &config, &mut notification_manager);
+                    if app.popup_repo == Some(repo) {
+                        app.close_popup(repo, WatcherCommand::Committed);
+                    } else {
+                        app.repos[repo]
+                            .commands_tx
+                            .send(WatcherCommand::Committed)
+                            .ok();
+                    }
+                    app.refresh_stash_list();
+                }
+                AnimationResult::LargeFiles(repo, action, paths) => {
+                    let repo_view = &app.repos[repo];
+                    let result = match action {
+                        LargeFileAction::Ignore => {
+                            git::large_files::ignore(&repo_view.path, &paths).map(|()| {
+                                format!(
+                                    "Added {} file(s) to .gitignore in {}",
+                                    paths.len(),
+                                    repo_view.name
+                                )
+                            })
+                        }
+                        LargeFileAction::Lfs => {
+                            git::large_files::track_with_lfs(&repo_view.path, &paths).map(|()| {
+                                format!(
+                                    "{} file(s) in {} go through Git LFS from the next commit on",
+                                    paths.len(),
+                                    repo_view.name
+                                )
+                            })
+                        }
+                    };
+                    match result {
+                        Ok(message) => notification_manager.add_notif(message),
+                        Err(e) => notification_manager
+                            .add_notif(format!("Couldn't do that in {}: {:#}", repo_view.name, e)),
+                    }
+                }
+                AnimationResult::Undo(repo, plan) => {
+                    let repo_view = &app.repos[repo];
+                    match git::undo::undo(&repo_view.path, &plan) {
+                        Ok(()) => notification_manager.add_notif(format!(
+                            "Undid {} in {}, its changes are staged again",
+                            plan.short_id, repo_view.name
+                        )),
+                        Err(e) => notification_manager
+                            .add_notif(format!("Undo in {} failed: {}", repo_view.name, e)),
+                    }
                 }
                 AnimationResult::Quit => {
                     break;
                 }
             }
+            app.refresh_history();
         }
 
         frame_index = (frame_index + 1) % animation::get_frame_count(config.shiggy_mode);
@@ -103,6 +336,73 @@ fn run_app(config: Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// A failed commit shouldn't take the whole TUI down with it, so errors end up
+/// in the notification panel instead. So do the branch a protected one got
+/// swapped for and the files that were too big to commit. `None` when
+/// nothing was committed.
+fn commit_repo(
+    repo: &RepoView,
+    request: &CommitRequest,
+    config: &Config,
+    notification_manager: &mut NotificationManager,
+) -> Option<CommitOutcome> {
+    match git::perform_commit(
+        &repo.path,
+        request,
+        &config.protection,
+        notification_manager,
+    ) {
+        Ok(outcome) => {
+            let skipped = outcome.skipped();
+            if !skipped.is_empty() {
+                let list: Vec<String> = skipped.iter().map(|f| format!("  {}", f)).collect();
+                notification_manager.add_notif(format!(
+                    "Left {} file(s) in {} unstaged:\n{}",
+                    skipped.len(),
+                    repo.name,
+                    list.join("\n")
+                ));
+            }
+            Some(outcome)
+        }
+        Err(e) => {
+            notification_manager.add_notif(format!("Commit in {} failed: {}", repo.name, e));
+            None
+        }
+    }
+}
+
+fn stash_repo(
+    repo: &RepoView,
+    action: StashAction,
+    config: &Config,
+    notification_manager: &mut NotificationManager,
+) {
+    let result = match action {
+        StashAction::Save => git::stash::save(&repo.path, config.message_style)
+            .map(|summary| format!("Stashed changes in {}: {}", repo.name, summary)),
+        StashAction::Apply(index) => git::stash::apply(&repo.path, index)
+            .map(|()| format!("Applied stash@{{{}}} in {}", index, repo.name)),
+        StashAction::Drop(index) => git::stash::drop(&repo.path, index)
+            .map(|()| format!("Dropped stash@{{{}}} in {}", index, repo.name)),
+    };
+    match result {
+        Ok(message) => notification_manager.add_notif(message),
+        Err(e) => notification_manager.add_notif(format!("Stash in {} failed: {}", repo.name, e)),
+    }
+}
+
+fn snapshot_repo(repo: &RepoView, notification_manager: &mut NotificationManager) {
+    match git::snapshot::take_snapshot(&repo.path) {
+        Ok(Some((ref_name, _))) => notification_manager
+            .add_notif(format!("Snapshot of {} saved to {}", repo.name, ref_name)),
+        Ok(None) => {}
+        Err(e) => {
+            notification_manager.add_notif(format!("Snapshot in {} failed: {}", repo.name, e))
+        }
+    }
+}
+
 fn show_prank() -> Result<(), Box<dyn Error>> {
     use crossterm::{cursor, execute, style, terminal};
     use std::io::stdout;
@@ -0,0 +1,400 @@
+use crate::git::history::{self, HistoryEntry};
+use crate::git::hunks::{self, Hunk};
+use crate::git::large_files::SkippedFile;
+use crate::git::message::MessageStyle;
+use crate::git::protect::BranchProtection;
+use crate::git::remote::Tracking;
+use crate::git::squash::{self, SquashPlan};
+use crate::git::stash::{self, StashEntry};
+use crate::git::state::Blocker;
+use crate::git::undo::{self, UndoPlan};
+use crate::git::{self, ChangeKind, FileChange, GitStats, RepoStatus};
+use crate::tui::events::{PopupFocus, PopupSelection};
+use crate::tui::input::TextInput;
+use crate::tui::timeline::Timeline;
+use crate::watcher::WatcherCommand;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// How many commits the history panel lists.
+const HISTORY_LEN: usize = 20;
+
+pub struct RepoView {
+    pub name: String,
+    pub path: PathBuf,
+    pub status: RepoStatus,
+    pub stats: Option<GitStats>,
+    pub files: Vec<FileChange>,
+    pub needs_commit: bool,
+    pub tracking: Option<Tracking>,
+    /// Set while a merge, rebase and the like is in progress. The popup shows
+    /// this instead of committing.
+    pub blocker: Option<Blocker>,
+    /// Commits have been waiting for a push for longer than the loop delay.
+    pub needs_push: bool,
+    /// The skipped files the large files popup last asked about.
+    pub offered_large_files: Vec<PathBuf>,
+    pub commands_tx: Sender<WatcherCommand>,
+}
+
+impl RepoView {
+    pub fn new(path: PathBuf, commands_tx: Sender<WatcherCommand>) -> Self {
+        Self {
+            name: git::repo_name(&path),
+            path,
+            status: RepoStatus::Clean,
+            stats: None,
+            files: Vec::new(),
+            needs_commit: false,
+            tracking: None,
+            blocker: None,
+            needs_push: false,
+            offered_large_files: Vec::new(),
+            commands_tx,
+        }
+    }
+}
+
+pub struct PickerEntry {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    pub staged: bool,
+    pub checked: bool,
+    /// A submodule with changes inside, picking it commits those first.
+    pub submodule: bool,
+}
+
+/// The diff of one file from the picker, with a stage/skip flag per hunk.
+pub struct HunkView {
+    pub path: PathBuf,
+    pub hunks: Vec<Hunk>,
+    pub selected: Vec<bool>,
+    pub cursor: usize,
+}
+
+/// Squashing the trailing autosave commits. `plan` holds the reason when
+/// there is nothing to squash, which the view shows instead of an editor.
+pub struct SquashView {
+    pub repo: usize,
+    pub plan: Result<SquashPlan, String>,
+    pub message: TextInput,
+}
+
+pub struct StashListView {
+    pub repo: usize,
+    pub entries: Vec<StashEntry>,
+    pub cursor: usize,
+    /// Dropping can't be undone, so it asks first.
+    pub confirm_drop: bool,
+}
+
+/// Asking before taking back the last commit. `plan` holds the reason when
+/// it can't be undone.
+pub struct UndoView {
+    pub repo: usize,
+    pub plan: Result<UndoPlan, String>,
+}
+
+/// Files autosave left unstaged, with the offer to ignore them or move them
+/// to Git LFS.
+pub struct LargeFilesView {
+    pub repo: usize,
+    pub files: Vec<SkippedFile>,
+}
+
+pub struct App {
+    pub repos: Vec<RepoView>,
+    pub selected_repo: usize,
+    pub popup_repo: Option<usize>,
+    pub popup_selection: PopupSelection,
+    pub popup_focus: PopupFocus,
+    pub commit_message: TextInput,
+    /// Once the user typed something we stop replacing the message when the
+    /// file selection changes.
+    pub message_edited: bool,
+    pub message_style: MessageStyle,
+    /// Squashing refuses on these branches.
+    pub protection: BranchProtection,
+    pub picker: Vec<PickerEntry>,
+    pub picker_cursor: usize,
+    /// Files where only some hunks are picked, see `CommitRequest::hunks`.
+    pub hunk_selection: HashMap<PathBuf, Vec<bool>>,
+    pub hunk_view: Option<HunkView>,
+    pub files_scroll: usize,
+    pub timeline: Option<Timeline>,
+    pub squash: Option<SquashView>,
+    pub stash_list: Option<StashListView>,
+    pub large_files: Option<LargeFilesView>,
+    pub undo: Option<UndoView>,
+    /// Recent commits of the selected repo, `None` while the panel is hidden.
+    pub history: Option<Vec<HistoryEntry>>,
+}
+
+impl App {
+    pub fn new(
+        repos: Vec<RepoView>,
+        message_style: MessageStyle,
+        protection: BranchProtection,
+    ) -> Self {
+        Self {
+            repos,
+            selected_repo: 0,
+            popup_repo: None,
+            popup_selection: PopupSelection::Yes,
+            popup_focus: PopupFocus::Buttons,
+            commit_message: TextInput::new(""),
+            message_edited: false,
+            message_style,
+            protection,
+            picker: Vec::new(),
+            picker_cursor: 0,
+            hunk_selection: HashMap::new(),
+            hunk_view: None,
+            files_scroll: 0,
+            timeline: None,
+            squash: None,
+            stash_list: None,
+            large_files: None,
+            undo: None,
+            history: None,
+        }
+    }
+
+    pub fn request_commit(&mut self, repo: usize) {
+        self.repos[repo].needs_commit = true;
+        if self.popup_repo.is_none() {
+            self.open_popup(repo);
+        }
+    }
+
+    pub fn open_popup(&mut self, repo: usize) {
+        self.popup_repo = Some(repo);
+        self.popup_selection = PopupSelection::Yes;
+        self.popup_focus = PopupFocus::Buttons;
+
+        // like git: if something is staged that's what gets committed,
+        // otherwise everything is
+        let files = &self.repos[repo].files;
+        let anything_staged = files.iter().any(|f| f.staged);
+        self.picker = files
+            .iter()
+            .map(|f| PickerEntry {
+                path: f.path.clone(),
+                kind: f.kind,
+                staged: f.staged,
+                checked: f.staged || !anything_staged,
+                submodule: f.submodule.is_some(),
+            })
+            .collect();
+        self.picker.sort_by(|a, b| a.path.cmp(&b.path));
+        self.picker_cursor = 0;
+        self.hunk_selection.clear();
+        self.hunk_view = None;
+
+        self.message_edited = false;
+        self.refresh_suggestion(repo);
+    }
+
+    pub fn selected_paths(&self) -> Vec<PathBuf> {
+        self.picker
+            .iter()
+            .filter(|e| e.checked)
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    fn refresh_suggestion(&mut self, repo: usize) {
+        if self.message_edited {
+            return;
+        }
+        let paths = self.selected_paths();
+        let suggestion =
+            git::suggest_message(&self.repos[repo].path, self.message_style, Some(&paths))
+                .unwrap_or_default();
+        self.commit_message.set_text(&suggestion);
+    }
+
+    /// Toggling a file picks or drops it as a whole, forgetting any hunks
+    /// that were picked for it.
+    pub fn toggle_picked(&mut self, repo: usize) {
+        if let Some(entry) = self.picker.get_mut(self.picker_cursor) {
+            entry.checked = !entry.checked;
+            self.hunk_selection.remove(&entry.path);
+            self.refresh_suggestion(repo);
+        }
+    }
+
+    /// Checks everything, or unchecks everything when it's all checked already.
+    pub fn toggle_all_picked(&mut self, repo: usize) {
+        let check = !self.picker.iter().all(|e| e.checked);
+        for entry in &mut self.picker {
+            entry.checked = check;
+        }
+        self.hunk_selection.clear();
+        self.refresh_suggestion(repo);
+    }
+
+    pub fn is_partially_picked(&self, path: &Path) -> bool {
+        self.hunk_selection.contains_key(path)
+    }
+
+    /// Opens the hunk view for the file under the picker cursor. Only files
+    /// that are already tracked have hunks against the index, and a submodule
+    /// is committed as a whole.
+    pub fn open_hunk_view(&mut self, repo: usize) {
+        let Some(entry) = self.picker.get(self.picker_cursor) else {
+            return;
+        };
+        if entry.kind != ChangeKind::Modified || entry.submodule {
+            return;
+        }
+        let hunks = match hunks::load_hunks(&self.repos[repo].path, &entry.path) {
+            Ok(hunks) if !hunks.is_empty() => hunks,
+            _ => return,
+        };
+        let selected = match self.hunk_selection.get(&entry.path) {
+            Some(selected) if selected.len() == hunks.len() => selected.clone(),
+            _ => vec![entry.checked; hunks.len()],
+        };
+        self.hunk_view = Some(HunkView {
+            path: entry.path.clone(),
+            hunks,
+            selected,
+            cursor: 0,
+        });
+    }
+
+    /// Takes the hunk picks over into the file picker. All or nothing picked
+    /// is just a normal checked or unchecked file.
+    pub fn close_hunk_view(&mut self, repo: usize) {
+        let Some(view) = self.hunk_view.take() else {
+            return;
+        };
+        let any = view.selected.iter().any(|s| *s);
+        let all = view.selected.iter().all(|s| *s);
+        if let Some(entry) = self.picker.iter_mut().find(|e| e.path == view.path) {
+            entry.checked = any;
+        }
+        if any && !all {
+            self.hunk_selection.insert(view.path, view.selected);
+        } else {
+            self.hunk_selection.remove(&view.path);
+        }
+        self.refresh_suggestion(repo);
+    }
+
+    pub fn move_picker(&mut self, lines: isize) {
+        let max = self.picker.len().saturating_sub(1);
+        self.picker_cursor = self.picker_cursor.saturating_add_signed(lines).min(max);
+    }
+
+    /// Closes the popup for `repo`, hands `command` to its watcher and moves on
+    /// to the next repo that is still waiting for an answer.
+    pub fn close_popup(&mut self, repo: usize, command: WatcherCommand) {
+        self.repos[repo].needs_commit = false;
+        self.repos[repo].commands_tx.send(command).ok();
+        self.popup_repo = None;
+        if let Some(next) = self.repos.iter().position(|r| r.needs_commit) {
+            self.open_popup(next);
+        }
+    }
+
+    pub fn open_timeline(&mut self) {
+        let repo = self.selected_repo;
+        self.timeline = Some(Timeline::open(repo, &self.repos[repo].path));
+    }
+
+    pub fn open_squash(&mut self) {
+        let repo = self.selected_repo;
+        let plan = squash::plan(&self.repos[repo].path, self.message_style, &self.protection)
+            .map_err(|e| e.to_string());
+        let message = match &plan {
+            Ok(plan) => TextInput::new(&plan.message),
+            Err(_) => TextInput::new(""),
+        };
+        self.squash = Some(SquashView {
+            repo,
+            plan,
+            message,
+        });
+    }
+
+    pub fn open_stash_list(&mut self) {
+        let repo = self.selected_repo;
+        self.stash_list = Some(StashListView {
+            repo,
+            entries: stash::list(&self.repos[repo].path).unwrap_or_default(),
+            cursor: 0,
+            confirm_drop: false,
+        });
+    }
+
+    /// Reloads the stash list after an apply or drop, keeping the cursor in range.
+    pub fn refresh_stash_list(&mut self) {
+        let Some(view) = &mut self.stash_list else {
+            return;
+        };
+        view.entries = stash::list(&self.repos[view.repo].path).unwrap_or_default();
+        view.cursor = view.cursor.min(view.entries.len().saturating_sub(1));
+    }
+
+    pub fn open_undo(&mut self) {
+        let repo = self.selected_repo;
+        let plan = undo::plan(&self.repos[repo].path).map_err(|e| e.to_string());
+        self.undo = Some(UndoView { repo, plan });
+    }
+
+    /// Asks what to do with the files autosave left out, once per set of
+    /// files. Being asked again every loop about the same ones helps nobody.
+    pub fn open_large_files(&mut self, repo: usize, files: Vec<SkippedFile>) {
+        let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+        if paths == self.repos[repo].offered_large_files {
+            return;
+        }
+        self.repos[repo].offered_large_files = paths;
+        if !files.is_empty() {
+            self.large_files = Some(LargeFilesView { repo, files });
+        }
+    }
+
+    pub fn toggle_history(&mut self) {
+        if self.history.take().is_none() {
+            self.history = Some(Vec::new());
+            self.refresh_history();
+        }
+    }
+
+    /// Reloads the history panel, if it's open, for the selected repo.
+    pub fn refresh_history(&mut self) {
+        if self.history.is_some() {
+            let path = &self.repos[self.selected_repo].path;
+            self.history = Some(history::recent_commits(path, HISTORY_LEN).unwrap_or_default());
+        }
+    }
+
+    pub fn push(&mut self, repo: usize) {
+        self.repos[repo].commands_tx.send(WatcherCommand::Push).ok();
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.repos.is_empty() {
+            self.selected_repo = (self.selected_repo + 1) % self.repos.len();
+            self.files_scroll = 0;
+            self.refresh_history();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.repos.is_empty() {
+            self.selected_repo = (self.selected_repo + self.repos.len() - 1) % self.repos.len();
+            self.files_scroll = 0;
+            self.refresh_history();
+        }
+    }
+
+    pub fn scroll_files(&mut self, lines: isize) {
+        let max = self.repos[self.selected_repo].files.len().saturating_sub(1);
+        self.files_scroll = self.files_scroll.saturating_add_signed(lines).min(max);
+    }
+}
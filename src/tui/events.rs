@@ -1,48 +1,394 @@
+use crate::git::squash::SquashPlan;
+use crate::git::undo::UndoPlan;
+use crate::git::CommitRequest;
+use crate::tui::app::App;
 use crate::tui::events::AnimationResult::{Commit, Quit};
-use crossterm::event::{self, Event, KeyCode};
+use crate::tui::input::TextInput;
+use crate::tui::timeline::RestoreTarget;
+use crate::watcher::WatcherCommand;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use git2::Oid;
 use std::error::Error;
-use std::sync::mpsc::Sender;
+use std::path::PathBuf;
 use std::time::Duration;
 
 pub enum AnimationResult {
-    Commit,
+    Commit(usize, CommitRequest),
+    /// Restore a file, or with `None` the whole tree, from a snapshot.
+    Restore(usize, Oid, Option<PathBuf>),
+    Squash(usize, SquashPlan, String),
+    Stash(usize, StashAction),
+    LargeFiles(usize, LargeFileAction, Vec<PathBuf>),
+    /// Soft-reset the last kris commit away.
+    Undo(usize, UndoPlan),
     Quit,
 }
 
+pub enum StashAction {
+    /// Shelve every change, from the popup's "Stash" button.
+    Save,
+    /// Index on the stash stack, `stash@{n}`.
+    Apply(usize),
+    Drop(usize),
+}
+
+/// What to do with files autosave left unstaged.
+pub enum LargeFileAction {
+    /// Append them to `.gitignore`.
+    Ignore,
+    /// `git lfs track` them and stage the pointers.
+    Lfs,
+}
+
+/// The popup buttons, left to right.
 #[derive(Clone, Copy, PartialEq)]
 pub enum PopupSelection {
     Yes,
+    Stash,
     No,
 }
 
-pub fn handle_events(
-    show_popup: &mut bool,
-    popup_selection: &mut PopupSelection,
-    reset_timer_tx: &Sender<()>,
-) -> Result<Option<AnimationResult>, Box<dyn Error>> {
+impl PopupSelection {
+    fn left(self) -> Self {
+        match self {
+            PopupSelection::Yes | PopupSelection::Stash => PopupSelection::Yes,
+            PopupSelection::No => PopupSelection::Stash,
+        }
+    }
+
+    fn right(self) -> Self {
+        match self {
+            PopupSelection::Yes => PopupSelection::Stash,
+            PopupSelection::Stash | PopupSelection::No => PopupSelection::No,
+        }
+    }
+}
+
+/// Which part of the commit popup gets the keys. `Tab` cycles through them.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PopupFocus {
+    Files,
+    Message,
+    Buttons,
+}
+
+pub fn handle_events(app: &mut App) -> Result<Option<AnimationResult>, Box<dyn Error>> {
     if crossterm::event::poll(Duration::from_millis(10))? {
-        if let Event::Key(key) = event::read()? {
-            if *show_popup {
+        match event::read()? {
+            Event::Key(key) => {
+                if let Some(repo) = app.popup_repo {
+                    return Ok(handle_popup_key(app, repo, key));
+                }
+                if app.squash.is_some() {
+                    return Ok(handle_squash_key(app, key));
+                }
+                if app.timeline.is_some() {
+                    return Ok(handle_timeline_key(app, key));
+                }
+                if app.stash_list.is_some() {
+                    return Ok(handle_stash_list_key(app, key));
+                }
+                if app.large_files.is_some() {
+                    return Ok(handle_large_files_key(app, key));
+                }
+                if app.undo.is_some() {
+                    return Ok(handle_undo_key(app, key));
+                }
                 match key.code {
-                    KeyCode::Left | KeyCode::Char('y') => *popup_selection = PopupSelection::Yes,
-                    KeyCode::Right | KeyCode::Char('n') => *popup_selection = PopupSelection::No,
-                    KeyCode::Enter => match popup_selection {
-                        PopupSelection::Yes => return Ok(Some(Commit)),
-                        PopupSelection::No => {
-                            *show_popup = false;
-                            reset_timer_tx.send(()).ok();
-                        }
-                    },
-                    KeyCode::Char('q') | KeyCode::Char('Q') => {
-                        *show_popup = false;
-                        reset_timer_tx.send(()).ok();
-                    }
+                    KeyCode::Char('q') | KeyCode::Char('Q') => return Ok(Some(Quit)),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+                    KeyCode::Char('c') => app.open_popup(app.selected_repo),
+                    KeyCode::Char('s') => app.open_timeline(),
+                    KeyCode::Char('S') => app.open_squash(),
+                    KeyCode::Char('p') => app.push(app.selected_repo),
+                    KeyCode::Char('z') => app.open_stash_list(),
+                    KeyCode::Char('u') => app.open_undo(),
+                    KeyCode::Char('h') => app.toggle_history(),
+                    KeyCode::PageDown | KeyCode::Char('J') => app.scroll_files(5),
+                    KeyCode::PageUp | KeyCode::Char('K') => app.scroll_files(-5),
                     _ => {}
                 }
-            } else if key.code == KeyCode::Char('q') || key.code == KeyCode::Char('Q') {
-                return Ok(Some(Quit));
             }
+            Event::Paste(text)
+                if app.popup_repo.is_some() && app.popup_focus == PopupFocus::Message =>
+            {
+                app.commit_message.insert_str(&text);
+                app.message_edited = true;
+            }
+            Event::Paste(text) if app.popup_repo.is_none() => {
+                if let Some(view) = &mut app.squash {
+                    view.message.insert_str(&text);
+                }
+            }
+            _ => {}
         }
     }
     Ok(None)
 }
+
+fn handle_popup_key(app: &mut App, repo: usize, key: KeyEvent) -> Option<AnimationResult> {
+    // mid-rebase or conflicted, the popup only says so
+    if app.repos[repo].blocker.is_some() {
+        if matches!(
+            key.code,
+            KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('n')
+        ) {
+            app.close_popup(repo, WatcherCommand::Snooze);
+        }
+        return None;
+    }
+    if app.hunk_view.is_some() {
+        handle_hunk_key(app, repo, key);
+        return None;
+    }
+    if key.code == KeyCode::Tab {
+        app.popup_focus = match app.popup_focus {
+            PopupFocus::Files => PopupFocus::Message,
+            PopupFocus::Message => PopupFocus::Buttons,
+            PopupFocus::Buttons => PopupFocus::Files,
+        };
+        return None;
+    }
+    // commit straight from the editor without tabbing over to "Yes"
+    if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        return commit(app, repo);
+    }
+
+    match app.popup_focus {
+        PopupFocus::Files => match key.code {
+            KeyCode::Up | KeyCode::Char('k') => app.move_picker(-1),
+            KeyCode::Down | KeyCode::Char('j') => app.move_picker(1),
+            KeyCode::Char(' ') => app.toggle_picked(repo),
+            KeyCode::Char('a') => app.toggle_all_picked(repo),
+            KeyCode::Char('d') => app.open_hunk_view(repo),
+            KeyCode::Enter | KeyCode::Esc => app.popup_focus = PopupFocus::Buttons,
+            _ => {}
+        },
+        PopupFocus::Message => {
+            if key.code == KeyCode::Esc {
+                app.popup_focus = PopupFocus::Buttons;
+            } else if edit_text(&mut app.commit_message, key) {
+                app.message_edited = true;
+            }
+        }
+        PopupFocus::Buttons => match key.code {
+            KeyCode::Left => app.popup_selection = app.popup_selection.left(),
+            KeyCode::Right => app.popup_selection = app.popup_selection.right(),
+            KeyCode::Char('y') => app.popup_selection = PopupSelection::Yes,
+            KeyCode::Char('s') => app.popup_selection = PopupSelection::Stash,
+            KeyCode::Char('n') => app.popup_selection = PopupSelection::No,
+            KeyCode::Enter => match app.popup_selection {
+                PopupSelection::Yes => return commit(app, repo),
+                PopupSelection::Stash => {
+                    return Some(AnimationResult::Stash(repo, StashAction::Save))
+                }
+                PopupSelection::No => app.close_popup(repo, WatcherCommand::Snooze),
+            },
+            KeyCode::Char('e') => app.popup_focus = PopupFocus::Message,
+            KeyCode::Char('f') => app.popup_focus = PopupFocus::Files,
+            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
+                app.close_popup(repo, WatcherCommand::Snooze)
+            }
+            _ => {}
+        },
+    }
+    None
+}
+
+/// The editing keys shared by every message box. Returns whether the text
+/// itself changed, cursor movement doesn't count.
+fn edit_text(input: &mut TextInput, key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Enter => input.newline(),
+        KeyCode::Backspace => input.backspace(),
+        KeyCode::Delete => input.delete(),
+        KeyCode::Left => input.move_left(),
+        KeyCode::Right => input.move_right(),
+        KeyCode::Up => input.move_up(),
+        KeyCode::Down => input.move_down(),
+        KeyCode::Home => input.home(),
+        KeyCode::End => input.end(),
+        KeyCode::Char(c) => input.insert_char(c),
+        _ => {}
+    }
+    matches!(
+        key.code,
+        KeyCode::Enter | KeyCode::Backspace | KeyCode::Delete | KeyCode::Char(_)
+    )
+}
+
+fn handle_squash_key(app: &mut App, key: KeyEvent) -> Option<AnimationResult> {
+    let view = app.squash.as_mut()?;
+    if key.code == KeyCode::Esc {
+        app.squash = None;
+        return None;
+    }
+    if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        if view.message.is_blank() {
+            return None;
+        }
+        let view = app.squash.take()?;
+        let message = view.message.text();
+        return match view.plan {
+            Ok(plan) => Some(AnimationResult::Squash(view.repo, plan, message)),
+            Err(_) => None,
+        };
+    }
+    if view.plan.is_ok() {
+        edit_text(&mut view.message, key);
+    }
+    None
+}
+
+fn handle_stash_list_key(app: &mut App, key: KeyEvent) -> Option<AnimationResult> {
+    let view = app.stash_list.as_mut()?;
+    let selected = view.entries.get(view.cursor).map(|e| e.index);
+
+    if view.confirm_drop {
+        view.confirm_drop = false;
+        return match (key.code, selected) {
+            (KeyCode::Char('y') | KeyCode::Enter, Some(index)) => {
+                Some(AnimationResult::Stash(view.repo, StashAction::Drop(index)))
+            }
+            _ => None,
+        };
+    }
+
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => view.cursor = view.cursor.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => {
+            view.cursor = (view.cursor + 1).min(view.entries.len().saturating_sub(1))
+        }
+        KeyCode::Enter | KeyCode::Char('a') => {
+            if let Some(index) = selected {
+                return Some(AnimationResult::Stash(view.repo, StashAction::Apply(index)));
+            }
+        }
+        KeyCode::Char('d') if selected.is_some() => view.confirm_drop = true,
+        KeyCode::Char('q') | KeyCode::Esc => app.stash_list = None,
+        _ => {}
+    }
+    None
+}
+
+fn handle_large_files_key(app: &mut App, key: KeyEvent) -> Option<AnimationResult> {
+    let action = match key.code {
+        KeyCode::Char('i') => LargeFileAction::Ignore,
+        KeyCode::Char('l') => LargeFileAction::Lfs,
+        KeyCode::Char('q') | KeyCode::Char('n') | KeyCode::Esc => {
+            app.large_files = None;
+            return None;
+        }
+        _ => return None,
+    };
+    let view = app.large_files.take()?;
+    let paths = view.files.into_iter().map(|f| f.path).collect();
+    Some(AnimationResult::LargeFiles(view.repo, action, paths))
+}
+
+fn handle_undo_key(app: &mut App, key: KeyEvent) -> Option<AnimationResult> {
+    match key.code {
+        KeyCode::Char('y') | KeyCode::Enter => {
+            let view = app.undo.take()?;
+            view.plan
+                .ok()
+                .map(|plan| AnimationResult::Undo(view.repo, plan))
+        }
+        KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => {
+            app.undo = None;
+            None
+        }
+        _ => None,
+    }
+}
+
+fn handle_hunk_key(app: &mut App, repo: usize, key: KeyEvent) {
+    let Some(view) = app.hunk_view.as_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => view.cursor = view.cursor.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => {
+            view.cursor = (view.cursor + 1).min(view.hunks.len().saturating_sub(1))
+        }
+        KeyCode::Char(' ') => view.selected[view.cursor] = !view.selected[view.cursor],
+        KeyCode::Char('a') => {
+            let select = !view.selected.iter().all(|s| *s);
+            view.selected.iter_mut().for_each(|s| *s = select);
+        }
+        KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q') => app.close_hunk_view(repo),
+        _ => {}
+    }
+}
+
+fn handle_timeline_key(app: &mut App, key: KeyEvent) -> Option<AnimationResult> {
+    let timeline = app.timeline.as_mut()?;
+    let repo_path = app.repos[timeline.repo].path.clone();
+
+    if let Some(target) = &timeline.confirm {
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                let id = timeline.selected()?.id;
+                let path = match target {
+                    RestoreTarget::File(path) => Some(path.clone()),
+                    RestoreTarget::Tree => None,
+                };
+                timeline.confirm = None;
+                return Some(AnimationResult::Restore(timeline.repo, id, path));
+            }
+            KeyCode::Char('n') | KeyCode::Esc => timeline.confirm = None,
+            _ => {}
+        }
+        return None;
+    }
+
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => timeline.move_cursor(&repo_path, -1),
+        KeyCode::Down | KeyCode::Char('j') => timeline.move_cursor(&repo_path, 1),
+        KeyCode::Tab => timeline.files_focused = !timeline.files_focused,
+        // only ever the highlighted file, which needs the file list focused
+        KeyCode::Char('r') if !timeline.files_focused => {
+            timeline.files_focused = !timeline.files.is_empty()
+        }
+        KeyCode::Char('r') => {
+            if let Some(file) = timeline.selected_file() {
+                timeline.confirm = Some(RestoreTarget::File(file.path.clone()));
+            }
+        }
+        KeyCode::PageDown | KeyCode::Char('J') => timeline.scroll_diff(10),
+        KeyCode::PageUp | KeyCode::Char('K') => timeline.scroll_diff(-10),
+        KeyCode::Char('R') if !timeline.files.is_empty() => {
+            timeline.confirm = Some(RestoreTarget::Tree)
+        }
+        KeyCode::Char('q') | KeyCode::Esc => app.timeline = None,
+        _ => {}
+    }
+    None
+}
+
+/// Nothing picked or an empty message would make an empty commit, send the
+/// user back to whatever is missing instead.
+fn commit(app: &mut App, repo: usize) -> Option<AnimationResult> {
+    let paths = app.selected_paths();
+    if paths.is_empty() {
+        app.popup_focus = PopupFocus::Files;
+        return None;
+    }
+    if app.commit_message.is_blank() {
+        app.popup_focus = PopupFocus::Message;
+        return None;
+    }
+    Some(Commit(
+        repo,
+        CommitRequest {
+            message: Some(app.commit_message.text()),
+            style: app.message_style,
+            paths: Some(paths),
+            hunks: app.hunk_selection.clone(),
+            autosave: false,
+            limits: None,
+            submodules: true,
+        },
+    ))
+}
@@ -0,0 +1,143 @@
+/// A small multi-line text buffer for the commit popup. `col` counts chars,
+/// not bytes, so the cursor never lands in the middle of a multi-byte char.
+pub struct TextInput {
+    lines: Vec<String>,
+    row: usize,
+    col: usize,
+}
+
+impl TextInput {
+    pub fn new(text: &str) -> Self {
+        let mut input = Self {
+            lines: vec![String::new()],
+            row: 0,
+            col: 0,
+        };
+        input.set_text(text);
+        input
+    }
+
+    /// Replaces the whole buffer and puts the cursor at the end of the first
+    /// line, which is where the summary usually needs tweaking.
+    pub fn set_text(&mut self, text: &str) {
+        self.lines = text.split('\n').map(str::to_string).collect();
+        self.row = 0;
+        self.col = self.line_len(0);
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.lines.iter().all(|l| l.trim().is_empty())
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    fn byte_index(&self) -> usize {
+        self.lines[self.row]
+            .char_indices()
+            .nth(self.col)
+            .map(|(i, _)| i)
+            .unwrap_or(self.lines[self.row].len())
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        if c == '\n' {
+            self.newline();
+            return;
+        }
+        let idx = self.byte_index();
+        self.lines[self.row].insert(idx, c);
+        self.col += 1;
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        // terminals love sending \r\n on paste
+        for c in text.chars().filter(|c| *c != '\r') {
+            self.insert_char(c);
+        }
+    }
+
+    pub fn newline(&mut self) {
+        let idx = self.byte_index();
+        let rest = self.lines[self.row].split_off(idx);
+        self.row += 1;
+        self.col = 0;
+        self.lines.insert(self.row, rest);
+    }
+
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let idx = self.byte_index();
+            self.lines[self.row].remove(idx);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.col < self.line_len(self.row) {
+            let idx = self.byte_index();
+            self.lines[self.row].remove(idx);
+        } else if self.row + 1 < self.lines.len() {
+            let next = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&next);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if self.col < self.line_len(self.row) {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.col = self.line_len(self.row);
+    }
+}
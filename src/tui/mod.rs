@@ -1,5 +1,8 @@
 pub mod animation;
+pub mod app;
 pub mod events;
+pub mod input;
 pub mod notifications;
 pub mod terminal;
+pub mod timeline;
 pub mod ui;
@@ -0,0 +1,101 @@
+use crate::git::hunks::Hunk;
+use crate::git::snapshot::{self, Snapshot, SnapshotFile};
+use std::path::{Path, PathBuf};
+
+/// What the confirmation prompt is about to restore.
+#[derive(Clone, PartialEq)]
+pub enum RestoreTarget {
+    File(PathBuf),
+    Tree,
+}
+
+/// The snapshot browser of one repo. The file list and the diff always belong
+/// to the snapshot under `cursor` and are reloaded when it moves.
+pub struct Timeline {
+    pub repo: usize,
+    pub snapshots: Vec<Snapshot>,
+    pub cursor: usize,
+    pub files: Vec<SnapshotFile>,
+    pub file_cursor: usize,
+    pub hunks: Vec<Hunk>,
+    /// First line of the diff pane that's shown.
+    pub diff_scroll: usize,
+    /// Keys move through the files instead of the snapshots.
+    pub files_focused: bool,
+    pub confirm: Option<RestoreTarget>,
+}
+
+impl Timeline {
+    pub fn open(repo: usize, repo_path: &Path) -> Self {
+        let mut timeline = Self {
+            repo,
+            snapshots: snapshot::list_snapshots(repo_path).unwrap_or_default(),
+            cursor: 0,
+            files: Vec::new(),
+            file_cursor: 0,
+            hunks: Vec::new(),
+            diff_scroll: 0,
+            files_focused: false,
+            confirm: None,
+        };
+        timeline.load_files(repo_path);
+        timeline
+    }
+
+    /// Reloads everything after a restore, staying on the same snapshot.
+    pub fn refresh(&mut self, repo_path: &Path) {
+        let current = self.selected().map(|s| s.id);
+        self.snapshots = snapshot::list_snapshots(repo_path).unwrap_or_default();
+        self.cursor = current
+            .and_then(|id| self.snapshots.iter().position(|s| s.id == id))
+            .unwrap_or(0);
+        self.load_files(repo_path);
+    }
+
+    pub fn selected(&self) -> Option<&Snapshot> {
+        self.snapshots.get(self.cursor)
+    }
+
+    pub fn selected_file(&self) -> Option<&SnapshotFile> {
+        self.files.get(self.file_cursor)
+    }
+
+    pub fn move_cursor(&mut self, repo_path: &Path, lines: isize) {
+        if self.files_focused {
+            let max = self.files.len().saturating_sub(1);
+            self.file_cursor = self.file_cursor.saturating_add_signed(lines).min(max);
+            self.load_hunks(repo_path);
+        } else {
+            let max = self.snapshots.len().saturating_sub(1);
+            self.cursor = self.cursor.saturating_add_signed(lines).min(max);
+            self.load_files(repo_path);
+        }
+    }
+
+    /// Scrolls the diff pane, never past its last line.
+    pub fn scroll_diff(&mut self, lines: isize) {
+        let total: usize = self.hunks.iter().map(|h| h.lines.len() + 1).sum();
+        let max = total.saturating_sub(1);
+        self.diff_scroll = self.diff_scroll.saturating_add_signed(lines).min(max);
+    }
+
+    fn load_files(&mut self, repo_path: &Path) {
+        self.files = match self.selected() {
+            Some(snapshot) => snapshot::snapshot_files(repo_path, snapshot.id).unwrap_or_default(),
+            None => Vec::new(),
+        };
+        self.file_cursor = 0;
+        self.load_hunks(repo_path);
+    }
+
+    fn load_hunks(&mut self, repo_path: &Path) {
+        self.hunks = match (self.selected(), self.selected_file()) {
+            (Some(snapshot), Some(file)) => {
+                snapshot::snapshot_file_hunks(repo_path, snapshot.id, &file.path)
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+        self.diff_scroll = 0;
+    }
+}
@@ -1,6 +1,15 @@
+use crate::git::history::HistoryEntry;
+use crate::git::hunks::HunkLine;
+use crate::git::snapshot::Snapshot;
+use crate::git::{FileChange, RepoStatus};
 use crate::tui::animation;
-use crate::tui::events::PopupSelection;
+use crate::tui::app::{
+    App, HunkView, LargeFilesView, RepoView, SquashView, StashListView, UndoView,
+};
+use crate::tui::events::{PopupFocus, PopupSelection};
+use crate::tui::input::TextInput;
 use crate::tui::notifications::Notification;
+use crate::tui::timeline::{RestoreTarget, Timeline};
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     prelude::*,
@@ -8,7 +17,7 @@ use ratatui::{
 };
 use std::collections::VecDeque;
 use std::error::Error;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -48,18 +57,22 @@ fn format_duration(duration: Duration) -> String {
         format!("{} minutes", total_seconds / 60)
     } else if total_seconds < 7200 {
         "1 hour".to_string()
-    } else {
+    } else if total_seconds < 2 * 86400 {
         format!("{} hours", total_seconds / 3600)
+    } else {
+        format!("{} days", total_seconds / 86400)
     }
 }
 
-fn draw_commit_popup(f: &mut Frame, selected: &PopupSelection, loop_delay: Duration) {
+fn draw_commit_popup(f: &mut Frame, app: &App, repo: usize, loop_delay: Duration) {
     let area = f.area();
-    let popup_area = centered_rect(25, 15, area);
+    let popup_area = centered_rect(60, 70, area);
+    let selected = &app.popup_selection;
 
     f.render_widget(Clear, popup_area);
 
     let popup_block = Block::default()
+        .title(app.repos[repo].name.clone())
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .style(Style::default().fg(Color::Rgb(255, 255, 255)));
@@ -67,9 +80,37 @@ fn draw_commit_popup(f: &mut Frame, selected: &PopupSelection, loop_delay: Durat
     let inner_area = popup_block.inner(popup_area);
     f.render_widget(popup_block, popup_area);
 
+    if let Some(blocker) = &app.repos[repo].blocker {
+        let mut text: Vec<Line> = vec![
+            Line::from("ayo! you have uncommitted changes, but kris won't commit them yet:"),
+            Line::from(""),
+        ];
+        text.extend(
+            blocker
+                .to_string()
+                .lines()
+                .map(|l| Line::styled(l.to_string(), Style::default().fg(Color::Yellow))),
+        );
+        text.push(Line::from(""));
+        text.push(Line::styled(
+            "Enter or Esc to come back later",
+            Style::default().fg(Color::Rgb(128, 128, 128)),
+        ));
+        f.render_widget(Paragraph::new(text).wrap(Wrap { trim: false }), inner_area);
+        return;
+    }
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+        .constraints(
+            [
+                Constraint::Length(2),
+                Constraint::Percentage(40),
+                Constraint::Min(3),
+                Constraint::Length(3),
+            ]
+            .as_ref(),
+        )
         .split(inner_area);
 
     let question_text = format!(
@@ -82,41 +123,204 @@ fn draw_commit_popup(f: &mut Frame, selected: &PopupSelection, loop_delay: Durat
         .style(Style::default().fg(Color::Rgb(255, 255, 255)));
     f.render_widget(question, chunks[0]);
 
+    draw_file_picker(f, app, chunks[1]);
+    draw_message_editor(f, app, chunks[2]);
+
     let button_chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .constraints(
+            [
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+                Constraint::Ratio(1, 3),
+            ]
+            .as_ref(),
+        )
         .horizontal_margin(1)
-        .split(chunks[1]);
+        .split(chunks[3]);
 
-    let yes_style = if *selected == PopupSelection::Yes {
-        Style::default().fg(Color::Rgb(255, 255, 255))
-    } else {
-        Style::default().fg(Color::Rgb(128, 128, 128))
-    };
-    let yes_button = Paragraph::new("Yes")
-        .style(yes_style)
-        .alignment(Alignment::Center)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_type(BorderType::Rounded),
-        );
-    f.render_widget(yes_button, button_chunks[0]);
+    let buttons = [
+        ("Yes", PopupSelection::Yes),
+        ("Stash", PopupSelection::Stash),
+        ("No", PopupSelection::No),
+    ];
+    for (i, (label, selection)) in buttons.into_iter().enumerate() {
+        let style = if *selected == selection {
+            Style::default().fg(Color::Rgb(255, 255, 255))
+        } else {
+            Style::default().fg(Color::Rgb(128, 128, 128))
+        };
+        let button = Paragraph::new(label)
+            .style(style)
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded),
+            );
+        f.render_widget(button, button_chunks[i]);
+    }
+}
 
-    let no_style = if *selected == PopupSelection::No {
-        Style::default().fg(Color::Rgb(255, 255, 255))
+fn focus_color(focused: bool) -> Color {
+    if focused {
+        Color::Rgb(255, 255, 255)
     } else {
-        Style::default().fg(Color::Rgb(128, 128, 128))
+        Color::Rgb(128, 128, 128)
+    }
+}
+
+fn draw_file_picker(f: &mut Frame, app: &App, area: Rect) {
+    let focused = app.popup_focus == PopupFocus::Files;
+    let picked = app.picker.iter().filter(|e| e.checked).count();
+    let picker_block = Block::default()
+        .title(format!(
+            "Files {}/{} (Space to pick, a for all, d for hunks)",
+            picked,
+            app.picker.len()
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(focus_color(focused)));
+    let height = picker_block.inner(area).height as usize;
+
+    let text: Vec<Line> = app
+        .picker
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let checkbox = if !entry.checked {
+                "[ ] "
+            } else if app.is_partially_picked(&entry.path) {
+                "[~] "
+            } else {
+                "[x] "
+            };
+            let kind_color = if entry.staged {
+                Color::Green
+            } else {
+                Color::Red
+            };
+            let mut spans = vec![
+                Span::raw(checkbox),
+                Span::styled(
+                    format!("{} ", entry.kind.letter()),
+                    Style::default().fg(kind_color),
+                ),
+                Span::raw(entry.path.display().to_string()),
+            ];
+            if entry.submodule {
+                spans.push(Span::styled(
+                    " (submodule, commits inside it first)",
+                    Style::default().fg(Color::Rgb(128, 128, 128)),
+                ));
+            }
+            let line = Line::from(spans);
+            if focused && i == app.picker_cursor {
+                line.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        })
+        .collect();
+    let scroll = app.picker_cursor.saturating_sub(height.saturating_sub(1));
+    let picker = Paragraph::new(text)
+        .block(picker_block)
+        .scroll((scroll as u16, 0));
+    f.render_widget(picker, area);
+}
+
+fn diff_line(line: &HunkLine) -> Line<'_> {
+    let color = match line.origin {
+        '+' => Color::Green,
+        '-' => Color::Red,
+        _ => Color::Rgb(128, 128, 128),
     };
-    let no_button = Paragraph::new("No")
-        .style(no_style)
-        .alignment(Alignment::Center)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .border_type(BorderType::Rounded),
-        );
-    f.render_widget(no_button, button_chunks[1]);
+    Line::styled(
+        format!("    {}{}", line.origin, line.content),
+        Style::default().fg(color),
+    )
+}
+
+fn draw_hunk_view(f: &mut Frame, view: &HunkView) {
+    let area = centered_rect(80, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let picked = view.selected.iter().filter(|s| **s).count();
+    let block = Block::default()
+        .title(format!(
+            "{} - hunks {}/{} (Space to pick, a for all, Enter to go back)",
+            view.path.display(),
+            picked,
+            view.hunks.len()
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::Rgb(255, 255, 255)));
+    let height = block.inner(area).height as usize;
+
+    let mut text: Vec<Line> = Vec::new();
+    let mut cursor_line = 0;
+    for (i, hunk) in view.hunks.iter().enumerate() {
+        if i == view.cursor {
+            cursor_line = text.len();
+        }
+        let checkbox = if view.selected[i] { "[x] " } else { "[ ] " };
+        let header = Line::from(vec![
+            Span::raw(checkbox),
+            Span::styled(hunk.header.clone(), Style::default().fg(Color::Cyan)),
+        ]);
+        text.push(if i == view.cursor {
+            header.style(Style::default().add_modifier(Modifier::REVERSED))
+        } else {
+            header
+        });
+        text.extend(hunk.lines.iter().map(diff_line));
+    }
+
+    // keep the current hunk header near the top so its lines show below it
+    let max_scroll = text.len().saturating_sub(height);
+    let scroll = cursor_line.min(max_scroll);
+    let paragraph = Paragraph::new(text).block(block).scroll((scroll as u16, 0));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_message_editor(f: &mut Frame, app: &App, area: Rect) {
+    draw_text_input(
+        f,
+        &app.commit_message,
+        "Commit message (Tab to edit, Ctrl+S to commit)",
+        app.popup_focus == PopupFocus::Message,
+        area,
+    );
+}
+
+fn draw_text_input(f: &mut Frame, input: &TextInput, title: &str, focused: bool, area: Rect) {
+    let editor_block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(focus_color(focused)));
+    let editor_area = editor_block.inner(area);
+
+    // keep the cursor line in view
+    let (row, col) = input.cursor();
+    let scroll = row.saturating_sub(editor_area.height.saturating_sub(1) as usize);
+    let text: Vec<Line> = input
+        .lines()
+        .iter()
+        .map(|l| Line::from(l.as_str()))
+        .collect();
+    let editor = Paragraph::new(text)
+        .block(editor_block)
+        .scroll((scroll as u16, 0));
+    f.render_widget(editor, area);
+
+    if focused {
+        let x = editor_area.x + (col as u16).min(editor_area.width.saturating_sub(1));
+        let y = editor_area.y + (row - scroll) as u16;
+        f.set_cursor_position(Position::new(x, y));
+    }
 }
 
 fn draw_notifications(f: &mut Frame, notifs: &VecDeque<Notification>) {
@@ -124,11 +328,17 @@ fn draw_notifications(f: &mut Frame, notifs: &VecDeque<Notification>) {
         return;
     }
 
+    // hook output and the like span several lines
+    let text: Vec<Line> = notifs
+        .iter()
+        .flat_map(|n| n.message.lines().map(Line::from))
+        .collect();
+
     let notif_area = {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(notifs.len() as u16 + 2),
+                Constraint::Length(text.len() as u16 + 2),
                 Constraint::Min(0),
             ])
             .split(f.area());
@@ -142,20 +352,561 @@ fn draw_notifications(f: &mut Frame, notifs: &VecDeque<Notification>) {
         .title("Notifications")
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded);
-    let text: Vec<Line> = notifs
-        .iter()
-        .map(|n| Line::from(n.message.as_str()))
-        .collect();
     let paragraph = Paragraph::new(text).block(notif_block);
 
     f.render_widget(paragraph, notif_area);
 }
 
+fn repo_line(repo: &RepoView, selected: bool) -> Line<'_> {
+    let marker = if selected { "> " } else { "  " };
+    let mut spans = vec![Span::raw(marker), Span::raw(repo.name.as_str())];
+    match repo.stats {
+        Some(stats) => {
+            spans.push(Span::styled(
+                format!(" +{}", stats.insertions),
+                Style::default().fg(Color::Green),
+            ));
+            spans.push(Span::styled(
+                format!(" -{}", stats.deletions),
+                Style::default().fg(Color::Red),
+            ));
+            spans.push(Span::raw(format!(
+                " ({} staged, {} unstaged, {} new)",
+                stats.staged.files, stats.unstaged.files, stats.untracked.files
+            )));
+        }
+        None => spans.push(Span::styled(
+            " clean",
+            Style::default().fg(Color::Rgb(128, 128, 128)),
+        )),
+    }
+    if repo.status == RepoStatus::NoCommitsYet {
+        spans.push(Span::styled(
+            " no commits yet",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if let Some(tracking) = &repo.tracking {
+        let color = if repo.needs_push {
+            Color::Yellow
+        } else {
+            Color::Rgb(128, 128, 128)
+        };
+        spans.push(Span::styled(
+            format!(" ↑{} ↓{}", tracking.ahead, tracking.behind),
+            Style::default().fg(color),
+        ));
+    }
+    if repo.needs_commit {
+        spans.push(Span::styled(" !", Style::default().fg(Color::Yellow)));
+    }
+
+    let line = Line::from(spans);
+    if selected {
+        line.style(Style::default().add_modifier(Modifier::BOLD))
+    } else {
+        line
+    }
+}
+
+fn file_line(file: &FileChange) -> Line<'_> {
+    // same colours as `git status`: green once it's staged, red otherwise
+    let kind_color = if file.staged {
+        Color::Green
+    } else {
+        Color::Red
+    };
+    // a gitlink only ever changes by one line, what's inside says more
+    let (insertions, deletions, label) = match &file.submodule {
+        Some(stats) => (
+            stats.insertions,
+            stats.deletions,
+            format!(" submodule, {} files", stats.total_changes),
+        ),
+        None => (file.insertions, file.deletions, String::new()),
+    };
+    Line::from(vec![
+        Span::styled(
+            format!("{} ", file.kind.letter()),
+            Style::default().fg(kind_color),
+        ),
+        Span::raw(file.path.display().to_string()),
+        Span::styled(
+            format!(" +{}", insertions),
+            Style::default().fg(Color::Green),
+        ),
+        Span::styled(format!(" -{}", deletions), Style::default().fg(Color::Red)),
+        Span::styled(label, Style::default().fg(Color::Yellow)),
+        Span::styled(
+            format!(" {}", format_duration(file.first_seen.elapsed())),
+            Style::default().fg(Color::Rgb(128, 128, 128)),
+        ),
+    ])
+}
+
+fn draw_side_panel(f: &mut Frame, app: &App) {
+    let column = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Percentage(30)])
+        .split(f.area())[1];
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(app.repos.len() as u16 + 2),
+            Constraint::Min(0),
+        ])
+        .split(column);
+
+    let list_block = Block::default()
+        .title("Repos")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    let text: Vec<Line> = app
+        .repos
+        .iter()
+        .enumerate()
+        .map(|(i, repo)| repo_line(repo, i == app.selected_repo))
+        .collect();
+    f.render_widget(Paragraph::new(text).block(list_block), chunks[0]);
+
+    let files = &app.repos[app.selected_repo].files;
+    if files.is_empty() {
+        return;
+    }
+    let files_block = Block::default()
+        .title(format!("Changes ({})", files.len()))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    let text: Vec<Line> = files.iter().map(file_line).collect();
+    let paragraph = Paragraph::new(text)
+        .block(files_block)
+        .scroll((app.files_scroll as u16, 0));
+    f.render_widget(paragraph, chunks[1]);
+}
+
+fn history_line(entry: &HistoryEntry, now: i64) -> Line<'_> {
+    let age = Duration::from_secs(now.saturating_sub(entry.time).max(0) as u64);
+    let summary_style = if entry.by_kris {
+        Style::default().fg(Color::Magenta)
+    } else {
+        Style::default()
+    };
+    let mut spans = vec![
+        Span::styled(
+            format!("{} ", &entry.id.to_string()[..7]),
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::styled(
+            format!("{} ago ", format_duration(age)),
+            Style::default().fg(Color::Rgb(128, 128, 128)),
+        ),
+        Span::styled(
+            format!("{} ", entry.author),
+            Style::default().fg(Color::Cyan),
+        ),
+    ];
+    if entry.by_kris {
+        spans.push(Span::styled(
+            "[kris] ",
+            summary_style.add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans.push(Span::styled(entry.summary.as_str(), summary_style));
+    Line::from(spans)
+}
+
+/// Left column, opposite the repo list. The age of the last commit goes
+/// first and in colour, that's the whole point of kris.
+fn draw_history_panel(f: &mut Frame, app: &App, entries: &[HistoryEntry]) {
+    let column = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(35), Constraint::Min(0)])
+        .split(f.area())[0];
+    f.render_widget(Clear, column);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let headline = match entries.first() {
+        Some(last) => {
+            let age = Duration::from_secs(now.saturating_sub(last.time).max(0) as u64);
+            let color = if age < Duration::from_secs(15 * 60) {
+                Color::Green
+            } else if age < Duration::from_secs(60 * 60) {
+                Color::Yellow
+            } else {
+                Color::Red
+            };
+            Line::from(Span::styled(
+                format!("last commit {} ago", format_duration(age)),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ))
+        }
+        None => Line::from(Span::styled(
+            "no commits yet",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )),
+    };
+
+    let mut text = vec![headline, Line::from("")];
+    text.extend(entries.iter().map(|entry| history_line(entry, now)));
+    let block = Block::default()
+        .title(format!(
+            "{} - history (h to hide)",
+            app.repos[app.selected_repo].name
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    f.render_widget(
+        Paragraph::new(text).block(block).wrap(Wrap { trim: false }),
+        column,
+    );
+}
+
+fn snapshot_line(snapshot: &Snapshot, now: i64) -> Line<'_> {
+    let age = Duration::from_secs(now.saturating_sub(snapshot.time).max(0) as u64);
+    let source = if snapshot.autosave {
+        "autosave"
+    } else {
+        "snapshot"
+    };
+    Line::from(vec![
+        Span::styled(
+            format!("{} ", &snapshot.id.to_string()[..7]),
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::raw(format!("{} ago ", format_duration(age))),
+        Span::styled(
+            format!("+{}", snapshot.changes.insertions),
+            Style::default().fg(Color::Green),
+        ),
+        Span::styled(
+            format!(" -{}", snapshot.changes.deletions),
+            Style::default().fg(Color::Red),
+        ),
+        Span::raw(format!(" ({} files) ", snapshot.changes.files)),
+        Span::styled(source, Style::default().fg(Color::Rgb(128, 128, 128))),
+        Span::raw(format!(" {}", snapshot.summary)),
+    ])
+}
+
+fn draw_timeline(f: &mut Frame, app: &App, timeline: &Timeline) {
+    let area = centered_rect(90, 85, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(
+            "{} - snapshots (Tab to switch, J/K to scroll the diff, r to restore file, R to restore all, q to close)",
+            app.repos[timeline.repo].name
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::Rgb(255, 255, 255)));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(inner);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(35), Constraint::Min(3)])
+        .split(columns[1]);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let list_block = Block::default()
+        .title(format!("Timeline ({})", timeline.snapshots.len()))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(focus_color(!timeline.files_focused)));
+    let height = list_block.inner(columns[0]).height as usize;
+    let text: Vec<Line> = if timeline.snapshots.is_empty() {
+        vec![Line::from("no snapshots yet, try --snapshot or --autosave")]
+    } else {
+        timeline
+            .snapshots
+            .iter()
+            .enumerate()
+            .map(|(i, snapshot)| {
+                let line = snapshot_line(snapshot, now);
+                if i == timeline.cursor {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect()
+    };
+    let scroll = timeline.cursor.saturating_sub(height.saturating_sub(1));
+    f.render_widget(
+        Paragraph::new(text)
+            .block(list_block)
+            .scroll((scroll as u16, 0)),
+        columns[0],
+    );
+
+    let files_block = Block::default()
+        .title(format!(
+            "Differs from the work tree ({})",
+            timeline.files.len()
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(focus_color(timeline.files_focused)));
+    let height = files_block.inner(right[0]).height as usize;
+    let text: Vec<Line> = timeline
+        .files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            let line = Line::from(vec![
+                Span::raw(format!("{} ", file.kind.letter())),
+                Span::raw(file.path.display().to_string()),
+                Span::styled(
+                    format!(" +{}", file.insertions),
+                    Style::default().fg(Color::Green),
+                ),
+                Span::styled(
+                    format!(" -{}", file.deletions),
+                    Style::default().fg(Color::Red),
+                ),
+            ]);
+            // the file `r` restores stays marked while the snapshots have focus
+            match (i == timeline.file_cursor, timeline.files_focused) {
+                (true, true) => line.style(Style::default().add_modifier(Modifier::REVERSED)),
+                (true, false) => line.style(Style::default().add_modifier(Modifier::UNDERLINED)),
+                _ => line,
+            }
+        })
+        .collect();
+    let scroll = timeline
+        .file_cursor
+        .saturating_sub(height.saturating_sub(1));
+    f.render_widget(
+        Paragraph::new(text)
+            .block(files_block)
+            .scroll((scroll as u16, 0)),
+        right[0],
+    );
+
+    let diff_block = Block::default()
+        .title("Snapshot -> work tree")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    let mut text: Vec<Line> = Vec::new();
+    for hunk in &timeline.hunks {
+        text.push(Line::styled(
+            hunk.header.clone(),
+            Style::default().fg(Color::Cyan),
+        ));
+        text.extend(hunk.lines.iter().map(diff_line));
+    }
+    f.render_widget(
+        Paragraph::new(text)
+            .block(diff_block)
+            .scroll((timeline.diff_scroll as u16, 0)),
+        right[1],
+    );
+
+    if let Some(target) = &timeline.confirm {
+        draw_restore_prompt(f, timeline, target);
+    }
+}
+
+fn draw_restore_prompt(f: &mut Frame, timeline: &Timeline, target: &RestoreTarget) {
+    let area = centered_rect(50, 20, f.area());
+    f.render_widget(Clear, area);
+    let what = match target {
+        RestoreTarget::File(path) => path.display().to_string(),
+        RestoreTarget::Tree => format!("all {} files", timeline.files.len()),
+    };
+    let id = timeline
+        .selected()
+        .map(|s| s.id.to_string()[..7].to_string())
+        .unwrap_or_default();
+    let text = format!(
+        "Restore {} from {}? Your current files get snapshotted first. (y/n)",
+        what, id
+    );
+    let prompt = Paragraph::new(text).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .title("Restore")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+    f.render_widget(prompt, area);
+}
+
+fn draw_squash_view(f: &mut Frame, app: &App, view: &SquashView) {
+    let area = centered_rect(60, 50, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!("{} - squash autosaves", app.repos[view.repo].name))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::Rgb(255, 255, 255)));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let plan = match &view.plan {
+        Ok(plan) => plan,
+        Err(reason) => {
+            let text = format!("Can't squash: {}. (Esc to close)", reason);
+            f.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), inner);
+            return;
+        }
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(3)])
+        .split(inner);
+    let question = format!(
+        "Fold the last {} autosave commits into one? Ctrl+S to squash, Esc to cancel.",
+        plan.count
+    );
+    f.render_widget(
+        Paragraph::new(question).wrap(Wrap { trim: true }),
+        chunks[0],
+    );
+    draw_text_input(f, &view.message, "Commit message", true, chunks[1]);
+}
+
+fn draw_stash_list(f: &mut Frame, app: &App, view: &StashListView) {
+    let area = centered_rect(60, 50, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(
+            "{} - stashes (Enter to apply, d to drop, q to close)",
+            app.repos[view.repo].name
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::Rgb(255, 255, 255)));
+    let height = block.inner(area).height as usize;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let text: Vec<Line> = if view.entries.is_empty() {
+        vec![Line::from("nothing stashed")]
+    } else {
+        view.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let age = Duration::from_secs(now.saturating_sub(entry.time).max(0) as u64);
+                let line = Line::from(vec![
+                    Span::styled(
+                        format!("stash@{{{}}} ", entry.index),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::styled(
+                        format!("{} ago ", format_duration(age)),
+                        Style::default().fg(Color::Rgb(128, 128, 128)),
+                    ),
+                    Span::raw(entry.message.as_str()),
+                ]);
+                if i == view.cursor {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect()
+    };
+    let scroll = view.cursor.saturating_sub(height.saturating_sub(1));
+    f.render_widget(
+        Paragraph::new(text).block(block).scroll((scroll as u16, 0)),
+        area,
+    );
+
+    if view.confirm_drop {
+        let prompt_area = centered_rect(40, 15, f.area());
+        f.render_widget(Clear, prompt_area);
+        let prompt = Paragraph::new("Drop this stash for good? (y/n)")
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title("Drop")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::default().fg(Color::Yellow)),
+            );
+        f.render_widget(prompt, prompt_area);
+    }
+}
+
+fn draw_large_files(f: &mut Frame, app: &App, view: &LargeFilesView) {
+    let area = centered_rect(60, 40, f.area());
+    f.render_widget(Clear, area);
+
+    let mut text = vec![
+        Line::from("Autosave left these out of the commit:"),
+        Line::from(""),
+    ];
+    text.extend(view.files.iter().map(|file| {
+        Line::from(Span::styled(
+            format!("  {}", file),
+            Style::default().fg(Color::Yellow),
+        ))
+    }));
+    text.push(Line::from(""));
+    text.push(Line::from(
+        "i: add them to .gitignore, l: track them with Git LFS, Esc: leave them be",
+    ));
+
+    let block = Block::default()
+        .title(format!("{} - skipped files", app.repos[view.repo].name))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .style(Style::default().fg(Color::Rgb(255, 255, 255)));
+    f.render_widget(
+        Paragraph::new(text).block(block).wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn draw_undo_prompt(f: &mut Frame, app: &App, view: &UndoView) {
+    let area = centered_rect(50, 20, f.area());
+    f.render_widget(Clear, area);
+
+    let (text, color) = match &view.plan {
+        Ok(plan) => (
+            format!(
+                "Undo {} \"{}\"? Its changes stay staged and your files stay as they are. (y/n)",
+                plan.short_id, plan.summary
+            ),
+            Color::Yellow,
+        ),
+        Err(reason) => (
+            format!("Can't undo: {}. (Esc to close)", reason),
+            Color::Red,
+        ),
+    };
+    let prompt = Paragraph::new(text).wrap(Wrap { trim: true }).block(
+        Block::default()
+            .title(format!("{} - undo last commit", app.repos[view.repo].name))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(color)),
+    );
+    f.render_widget(prompt, area);
+}
+
 pub fn draw_ui(
     f: &mut Frame,
     frame_index: usize,
-    show_popup: bool,
-    popup_selection: &PopupSelection,
+    app: &App,
     notifications: &VecDeque<Notification>,
     loop_delay: Duration,
     shiggy_mode: bool,
@@ -187,8 +938,32 @@ pub fn draw_ui(
     let paragraph = Paragraph::new(ansi_text).block(Block::default());
     f.render_widget(paragraph, horizontal_layout[1]);
 
-    if show_popup {
-        draw_commit_popup(f, popup_selection, loop_delay);
+    draw_side_panel(f, app);
+    if let Some(entries) = &app.history {
+        draw_history_panel(f, app, entries);
+    }
+
+    if let Some(timeline) = &app.timeline {
+        draw_timeline(f, app, timeline);
+    }
+    if let Some(view) = &app.squash {
+        draw_squash_view(f, app, view);
+    }
+    if let Some(view) = &app.stash_list {
+        draw_stash_list(f, app, view);
+    }
+    if let Some(view) = &app.large_files {
+        draw_large_files(f, app, view);
+    }
+    if let Some(view) = &app.undo {
+        draw_undo_prompt(f, app, view);
+    }
+
+    if let Some(repo) = app.popup_repo {
+        draw_commit_popup(f, app, repo, loop_delay);
+        if let Some(view) = &app.hunk_view {
+            draw_hunk_view(f, view);
+        }
     }
 
     draw_notifications(f, notifications);
@@ -1,9 +1,25 @@
+use crate::git::state::Blocker;
 use crate::git::GitStats;
 
-pub fn get_commit_message() -> &'static str {
+pub fn get_commit_flavour() -> &'static str {
     "this commit is made by kwis uwu"
 }
 
+pub fn get_no_commits_body() -> &'static str {
+    "No commits yet?! >w< Make youw vewy fiwst commit, it's the most impowtant one!"
+}
+
+pub fn get_blocked_body(blocker: &Blocker) -> String {
+    format!("Hold on! >_< Kwis won't commit wight now, {}", blocker)
+}
+
+pub fn get_unpushed_body(ahead: usize) -> String {
+    format!(
+        "{} commit(s) onwy wive on this machine! >_< Push them befowe youw waptop expwodes, pwease!",
+        ahead
+    )
+}
+
 pub fn get_notification_body(current_stats: Option<GitStats>, previous_stats: Option<GitStats>) -> String {
     match current_stats {
         Some(current) => {
@@ -44,6 +60,19 @@ pub fn get_notification_body(current_stats: Option<GitStats>, previous_stats: Op
                         message.push_str("Wook at chu, coding away! Keep it up, nyaa! ");
                     }
                 }
+                if current.untracked.files > 0 {
+                    message.push_str(&format!(
+                        "{} new fiwe(s) nobody towd git about yet! ",
+                        current.untracked.files
+                    ));
+                }
+                if current.staged.files > 0
+                    && current.unstaged.files == 0
+                    && current.untracked.files == 0
+                {
+                    // everything is git add-ed already, just one step left
+                    message.push_str("Evewything is staged, just hit commit! ");
+                }
                 // do not forget to commit ur changes !!
                 if message.is_empty() || !message.contains("commit") {
                     message.push_str("Don't fowget to commit youw changes, pwease! ^w^ ");
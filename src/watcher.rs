@@ -0,0 +1,350 @@
+use crate::git::remote::{self, Tracking};
+use crate::git::state::{self, Blocker};
+use crate::git::{self, ChangeFilter, FileChange, GitStats, RepoStatus};
+use crate::uwu;
+use notify::event::{AccessKind, AccessMode, ModifyKind};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify_rust::Notification;
+use rodio::{OutputStream, Sink};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// How long the work tree has to stay quiet before we rescan, so a `cargo build`
+/// or a branch switch ends up as one scan instead of thousands.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+const EVENT_TICK: Duration = Duration::from_millis(100);
+
+pub enum WatcherEvent {
+    Stats {
+        repo: usize,
+        status: RepoStatus,
+        stats: Option<GitStats>,
+        files: Vec<FileChange>,
+        tracking: Option<Tracking>,
+        blocker: Option<Blocker>,
+    },
+    NeedsCommit {
+        repo: usize,
+    },
+    /// Commits have been sitting unpushed for longer than `loop_delay`.
+    NeedsPush {
+        repo: usize,
+    },
+    /// Where the branch was pushed to, or why it wasn't.
+    Pushed {
+        repo: usize,
+        result: Result<String, String>,
+    },
+}
+
+/// Sent from the TUI back to a repo's watcher. Both restart the countdown, the
+/// difference is only in how we got there.
+pub enum WatcherCommand {
+    /// The user answered "No", ask again after another `loop_delay`.
+    Snooze,
+    /// Something was just committed, rescan right away.
+    Committed,
+    /// Push the current branch. Done here so a slow remote doesn't freeze the TUI.
+    Push,
+}
+
+pub struct WatcherSettings {
+    pub loop_delay: Duration,
+    pub poll_interval: Duration,
+    pub audio_alert_mode: bool,
+    pub fs_events: bool,
+}
+
+/// Keeps the inotify (or whatever the platform has) watcher alive and collects
+/// the paths it reports until the burst of events settles down.
+struct FsEvents {
+    watcher: RecommendedWatcher,
+    /// Each path comes with whether it was just created or moved in.
+    paths_rx: Receiver<(PathBuf, bool)>,
+    filter: ChangeFilter,
+    last_change: Option<Instant>,
+}
+
+impl FsEvents {
+    fn new(repo_path: &Path) -> Option<Self> {
+        let filter = ChangeFilter::new(repo_path).ok()?;
+        let (paths_tx, paths_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                if is_write(&event.kind) {
+                    let created = is_created(&event.kind);
+                    for path in event.paths {
+                        paths_tx.send((path, created)).ok();
+                    }
+                }
+            }
+        })
+        .ok()?;
+        // a watch per directory instead of one recursive watch, which would
+        // spend the inotify limit on `target/` and `node_modules/`
+        watch_dirs(&mut watcher, &filter, filter.workdir()).ok()?;
+        watcher
+            .watch(filter.git_dir(), RecursiveMode::NonRecursive)
+            .ok()?;
+        let refs = filter.git_dir().join("refs");
+        if refs.is_dir() {
+            watcher.watch(&refs, RecursiveMode::Recursive).ok()?;
+        }
+
+        Some(Self {
+            watcher,
+            paths_rx,
+            filter,
+            last_change: None,
+        })
+    }
+
+    /// Returns true once something relevant changed and things went quiet
+    /// again. Fails when a new directory can't be watched.
+    fn settled(&mut self) -> notify::Result<bool> {
+        while let Ok((path, created)) = self.paths_rx.try_recv() {
+            // directories made after we started need watches of their own
+            if created && path.is_dir() {
+                watch_dirs(&mut self.watcher, &self.filter, &path)?;
+            }
+            if self.filter.is_relevant(&path) {
+                self.last_change = Some(Instant::now());
+            }
+        }
+        match self.last_change {
+            Some(at) if at.elapsed() >= DEBOUNCE => {
+                self.last_change = None;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+fn watch_dirs(
+    watcher: &mut RecommendedWatcher,
+    filter: &ChangeFilter,
+    dir: &Path,
+) -> notify::Result<()> {
+    for dir in filter.dirs_to_watch(dir) {
+        match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            // gone again before we got to it, nothing to watch there
+            Err(e) if matches!(e.kind, notify::ErrorKind::PathNotFound) => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Our own scans read files too, so plain accesses don't count as changes.
+fn is_write(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(access) => *access == AccessKind::Close(AccessMode::Write),
+        _ => true,
+    }
+}
+
+fn is_created(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+    )
+}
+
+/// Tracks how long the tree has been dirty so we only nag once the changes
+/// have been sitting around for longer than `loop_delay`.
+struct WatcherState {
+    dirty_since: Option<Instant>,
+    nagged: bool,
+}
+
+impl WatcherState {
+    fn update(&mut self, dirty: bool, loop_delay: Duration) -> bool {
+        if !dirty {
+            self.reset();
+            return false;
+        }
+
+        let dirty_since = *self.dirty_since.get_or_insert_with(Instant::now);
+        if !self.nagged && dirty_since.elapsed() >= loop_delay {
+            self.nagged = true;
+            return true;
+        }
+        false
+    }
+
+    fn reset(&mut self) {
+        self.dirty_since = None;
+        self.nagged = false;
+    }
+}
+
+pub fn git_watcher_loop(
+    repo: usize,
+    repo_path: PathBuf,
+    events_tx: Sender<WatcherEvent>,
+    commands_rx: Receiver<WatcherCommand>,
+    settings: WatcherSettings,
+) {
+    let repo_name = git::repo_name(&repo_path);
+    let mut previous_stats: Option<GitStats> = None;
+    let mut state = WatcherState {
+        dirty_since: None,
+        nagged: false,
+    };
+    // same countdown, but for commits that never left this machine
+    let mut unpushed = WatcherState {
+        dirty_since: None,
+        nagged: false,
+    };
+
+    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+    let sink = Sink::try_new(&stream_handle).unwrap();
+
+    // falls back to plain polling when the platform watcher can't be set up
+    let mut fs_events = if settings.fs_events {
+        FsEvents::new(&repo_path)
+    } else {
+        None
+    };
+    let mut tick = if fs_events.is_some() {
+        EVENT_TICK
+    } else {
+        settings.poll_interval
+    };
+
+    let mut current_stats: Option<GitStats> = None;
+    let mut status = RepoStatus::Clean;
+    let mut rescan = true;
+    let mut first_seen: HashMap<PathBuf, Instant> = HashMap::new();
+    let mut tracking: Option<Tracking> = None;
+    let mut blocker: Option<Blocker> = None;
+
+    loop {
+        if rescan {
+            let changes = git::get_git_diff_stats(&repo_path).ok().flatten();
+            current_stats = changes.as_ref().map(|c| c.stats);
+            status = git::get_repo_status(&repo_path, current_stats.is_some());
+
+            let mut files = changes.map(|c| c.files).unwrap_or_default();
+            first_seen.retain(|path, _| files.iter().any(|f| &f.path == path));
+            for file in &mut files {
+                file.first_seen = *first_seen
+                    .entry(file.path.clone())
+                    .or_insert(file.first_seen);
+            }
+
+            tracking = remote::tracking(&repo_path).ok().flatten();
+            blocker = state::commit_blocker(&repo_path).ok().flatten();
+            if events_tx
+                .send(WatcherEvent::Stats {
+                    repo,
+                    status,
+                    stats: current_stats,
+                    files,
+                    tracking: tracking.clone(),
+                    blocker: blocker.clone(),
+                })
+                .is_err()
+            {
+                break;
+            }
+            rescan = false;
+        }
+
+        if state.update(current_stats.is_some(), settings.loop_delay) {
+            send_notification(
+                &repo_name,
+                status,
+                blocker.as_ref(),
+                current_stats,
+                previous_stats,
+            );
+            previous_stats = current_stats;
+
+            if settings.audio_alert_mode && sink.empty() {
+                let mp3_data = include_bytes!("../sounds/yes.mp3");
+                if let Ok(source) = rodio::Decoder::new(Cursor::new(mp3_data)) {
+                    sink.append(source);
+                }
+            }
+            if events_tx.send(WatcherEvent::NeedsCommit { repo }).is_err() {
+                break;
+            }
+        }
+
+        let ahead = tracking.as_ref().map_or(0, |t| t.ahead);
+        if unpushed.update(ahead > 0, settings.loop_delay) {
+            send_unpushed_notification(&repo_name, ahead);
+            if events_tx.send(WatcherEvent::NeedsPush { repo }).is_err() {
+                break;
+            }
+        }
+
+        match commands_rx.recv_timeout(tick) {
+            Ok(WatcherCommand::Snooze) | Ok(WatcherCommand::Committed) => {
+                state.reset();
+                rescan = true;
+            }
+            Ok(WatcherCommand::Push) => {
+                let result = remote::push(&repo_path).map_err(|e| e.to_string());
+                if result.is_ok() {
+                    unpushed.reset();
+                }
+                if events_tx
+                    .send(WatcherEvent::Pushed { repo, result })
+                    .is_err()
+                {
+                    break;
+                }
+                rescan = true;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        rescan |= match fs_events.as_mut().map(FsEvents::settled) {
+            Some(Ok(settled)) => settled,
+            // out of inotify watches most likely, poll from here on
+            Some(Err(_)) => {
+                fs_events = None;
+                tick = settings.poll_interval;
+                true
+            }
+            None => true,
+        };
+    }
+}
+
+fn send_notification(
+    repo_name: &str,
+    status: RepoStatus,
+    blocker: Option<&Blocker>,
+    current_stats: Option<GitStats>,
+    previous_stats: Option<GitStats>,
+) {
+    let body = if let Some(blocker) = blocker {
+        uwu::get_blocked_body(blocker)
+    } else if status == RepoStatus::NoCommitsYet {
+        uwu::get_no_commits_body().to_string()
+    } else {
+        uwu::get_notification_body(current_stats, previous_stats)
+    };
+
+    Notification::new()
+        .summary(&format!("Kwis :3 - {} needs you", repo_name))
+        .body(&body)
+        .show()
+        .unwrap();
+}
+
+fn send_unpushed_notification(repo_name: &str, ahead: usize) {
+    Notification::new()
+        .summary(&format!("Kwis :3 - {} needs a push", repo_name))
+        .body(&uwu::get_unpushed_body(ahead))
+        .show()
+        .unwrap();
+}